@@ -2,7 +2,7 @@ pub fn alph(num: i32) -> String {
     let mut num = num;
     let mut res = Vec::<char>::new();
     while num > 0 {
-        num = num / 26;
+        num /= 26;
         let modnum = num % 26;
         res.push((modnum + 65) as u8 as char);
     }
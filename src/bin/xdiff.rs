@@ -1,55 +1,204 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
 use dialoguer::{theme::ColorfulTheme, Input, MultiSelect};
-use std::io::Write;
+use serde_json::json;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::process::ExitCode;
+use tokio::fs;
 use xdiff_live::{
-    cli::{Action, Args, RunArgs},
+    cli::{
+        Action, Args, CompletionsArgs, DiffRunArgs, ListArgs, OutputFormat, ParseArgs, PrintFormat,
+        ShowArgs, ValidateArgs,
+    },
+    completions,
     config::LoadConfig,
-    highlight_text, process_error_output, DiffConfig, DiffProfile, ExtraArgs, RequestProfile,
-    ResponseProfile,
+    highlight_text, html_report, junit_exit_status, junit_report, process_error_output,
+    prompt_vars, render_template, ConfigSource, DiffConfig, DiffProfile, ExitStatus, ExtraArgs,
+    InvalidConfig, RequestProfile, ResponseProfile, Retry, TestCase,
 };
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let args = Args::parse();
     // println!("{:?}", args);
     let result = match args.action {
         Action::Run(arg) => run(arg).await,
-        Action::Parse => parse().await,
+        Action::Parse(arg) => parse(arg).await,
+        Action::List(arg) => list(arg).await,
+        Action::Show(arg) => show(arg).await,
+        Action::Validate(arg) => validate(arg).await,
+        Action::Completions(arg) => print_completions(arg),
         _ => panic!("Not implemented"),
     };
 
     process_error_output(result)
 }
 
-async fn run(args: RunArgs) -> Result<()> {
-    let config_file = args
-        .config
-        .unwrap_or_else(|| "fixtures/test.yml".to_string());
-    // println!("Using config file: {}", config_file);
-    let config = DiffConfig::load_yaml(&config_file).await?;
+async fn run(args: DiffRunArgs) -> Result<ExitStatus> {
+    let source = ConfigSource::locate(args.run.config.as_deref(), "xdiff")?;
+    // println!("Using config file: {}", source);
+    if args.output == OutputFormat::Junit {
+        return run_junit(&args, &source).await;
+    }
+
+    let name = args.run.profile_name()?;
+    let config = DiffConfig::load(
+        &source,
+        args.run.config_format.unwrap_or(source.format()),
+        &args.run.vars,
+    )
+    .await?;
     // println!("{:?}", config);
-    let profile = config.get_profile(&args.profile).ok_or_else(|| {
-        anyhow::anyhow!(
+    let profile = config.get_profile(name).ok_or_else(|| {
+        InvalidConfig(anyhow::anyhow!(
             "Profile {} not found in config file {}",
-            args.profile,
-            config_file
-        )
+            name,
+            source
+        ))
     })?;
+    let profile = apply_overrides(profile, &args);
 
-    let extra_args = args.extra_params.into();
+    let extra_args = args.run.extra_params.clone().into();
     // into()是Rust中的一个通用方法，它用于执行转换（conversion）或转移（move）操作。
-    let output = profile.diff(extra_args).await?;
+    let (output, changed) = match args.output {
+        OutputFormat::Text => {
+            let output = profile.diff(extra_args).await?;
+            if args.run.verbose {
+                print_retries([&output.retries[0], &output.retries[1]])?;
+            }
+            (output.text, output.changed)
+        }
+        OutputFormat::Json => {
+            let report = profile.report(name, &extra_args).await?;
+            if args.run.verbose {
+                print_retries([&report.req1.retries, &report.req2.retries])?;
+            }
+            let output = format!("{}\n", serde_json::to_string_pretty(&report)?);
+            (output, report.changed)
+        }
+        OutputFormat::Html => {
+            let report = profile.report(name, &extra_args).await?;
+            if args.run.verbose {
+                print_retries([&report.req1.retries, &report.req2.retries])?;
+            }
+            let resolved = profile.resolve(&extra_args)?.masked();
+            (html_report(&report, &resolved)?, report.changed)
+        }
+        OutputFormat::Junit => unreachable!("junit output is handled by run_junit"),
+    };
 
-    let stdout = std::io::stdout();
-    let mut stdout = stdout.lock();
+    write_output(args.out.as_deref(), &output).await?;
 
-    write!(stdout, "{}", output)?;
+    if changed {
+        Ok(ExitStatus::Different)
+    } else {
+        Ok(ExitStatus::Ok)
+    }
+}
 
+/// diff every profile (or the one given by `-p`) and report each as a junit test case
+async fn run_junit(args: &DiffRunArgs, source: &ConfigSource) -> Result<ExitStatus> {
+    // profiles are loaded and validated one by one, so that a broken profile fails only its
+    // own test case
+    let format = args.run.config_format.unwrap_or(source.format());
+    let mut profiles =
+        DiffConfig::parse_each(&source.read().await?, source.path(), format, &args.run.vars)?;
+    if let Some(name) = &args.run.profile {
+        profiles.retain(|(n, _)| n == name);
+        if profiles.is_empty() {
+            let e = anyhow::anyhow!("Profile {} not found in config file {}", name, source);
+            return Err(InvalidConfig(e).into());
+        }
+    }
+    profiles.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let extra_args: ExtraArgs = args.run.extra_params.clone().into();
+    let mut cases = Vec::with_capacity(profiles.len());
+    for (name, config) in profiles {
+        let case = match config {
+            Ok(config) => {
+                let profile = apply_overrides(&config.profiles[&name], args);
+                TestCase::run(&name, &profile, &extra_args).await
+            }
+            Err(e) => TestCase::error(&name, e),
+        };
+        cases.push(case);
+    }
+
+    write_output(
+        args.out.as_deref(),
+        &junit_report(&source.to_string(), &cases),
+    )
+    .await?;
+    Ok(junit_exit_status(&cases))
+}
+
+/// apply the command line overrides of the response profile
+fn apply_overrides(profile: &DiffProfile, args: &DiffRunArgs) -> DiffProfile {
+    let mut profile = profile.clone();
+    if let Some(diff_mode) = args.diff_mode {
+        profile.res.diff_mode = diff_mode;
+    }
+    if let Some(layout) = args.layout {
+        profile.res.layout = layout;
+    }
+    args.run.override_request(&mut profile.req1);
+    args.run.override_request(&mut profile.req2);
+    profile
+}
+
+/// print the retried attempts of both requests to stderr
+fn print_retries(retries: [&[Retry]; 2]) -> Result<()> {
+    let stderr = std::io::stderr();
+    let mut stderr = stderr.lock();
+    for (name, retries) in ["req1", "req2"].into_iter().zip(retries) {
+        for retry in retries {
+            writeln!(stderr, "{}: {}", name, retry)?;
+        }
+    }
+    Ok(())
+}
+
+/// write the output to `out`, or stdout if not given
+async fn write_output(out: Option<&str>, output: &str) -> Result<()> {
+    match out {
+        Some(path) => fs::write(path, output)
+            .await
+            .with_context(|| format!("failed to write output to {}", path))?,
+        None => {
+            let stdout = std::io::stdout();
+            let mut stdout = stdout.lock();
+            write!(stdout, "{}", output)?;
+        }
+    }
     Ok(())
 }
 
-async fn parse() -> Result<()> {
+/// check every profile and report all the errors, exit with 2 if any
+async fn validate(args: ValidateArgs) -> Result<ExitStatus> {
+    let source = ConfigSource::locate(args.config.as_deref(), "xdiff")?;
+    let format = args.config_format.unwrap_or(source.format());
+    let check = DiffConfig::check_config(&source.read().await?, source.path(), format, &args.vars);
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    write!(stdout, "{}", check.render(&source.to_string()))?;
+    if check.is_ok() {
+        Ok(ExitStatus::Ok)
+    } else {
+        Ok(ExitStatus::ConfigError)
+    }
+}
+
+fn print_completions(args: CompletionsArgs) -> Result<ExitStatus> {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    write!(stdout, "{}", completions::<Args>(args.shell, "xdiff-live")?)?;
+    Ok(ExitStatus::Ok)
+}
+
+async fn parse(args: ParseArgs) -> Result<ExitStatus> {
     // println!("Parse not implemented");
     let theme = ColorfulTheme::default();
     let url1 = Input::<String>::with_theme(&theme)
@@ -59,8 +208,9 @@ async fn parse() -> Result<()> {
         .with_prompt("Url2")
         .interact_text()?;
 
-    let req1: RequestProfile = url1.parse()?;
-    let req2: RequestProfile = url2.parse()?;
+    let vars = prompt_vars(&theme, &[&url1, &url2])?;
+    let req1: RequestProfile = render_template(&url1, &vars)?.parse()?;
+    let req2: RequestProfile = render_template(&url2, &vars)?.parse()?;
 
     let name = Input::<String>::with_theme(&theme)
         .with_prompt("Profile")
@@ -78,16 +228,89 @@ async fn parse() -> Result<()> {
     let skip_headers = chosen.iter().map(|i| headers[*i].to_string()).collect();
 
     let res = ResponseProfile::new(skip_headers, vec![]);
-    let profile = DiffProfile::new(req1, req2, res);
+    // credentials of the urls are not printed
+    req1.warn_masked_credentials();
+    req2.warn_masked_credentials();
+    let profile = DiffProfile::new(req1, req2, res).masked();
     let config = DiffConfig::new(vec![(name, profile)].into_iter().collect());
-    let result = serde_yaml::to_string(&config)?;
+    let result = args.format.to_string(&config)?;
 
     let stdout = std::io::stdout();
     let mut stdout = stdout.lock();
     if atty::is(atty::Stream::Stdout) {
-        write!(stdout, "---\n{}", highlight_text(&result, "yaml", None)?)?;
+        let extension = args.format.extension();
+        write!(stdout, "{}", highlight_text(&result, extension, None)?)?;
     } else {
-        write!(stdout, "---\n{}", result)?;
+        write!(stdout, "{}", result)?;
     }
-    Ok(())
+    Ok(ExitStatus::Ok)
+}
+
+async fn list(args: ListArgs) -> Result<ExitStatus> {
+    let source = ConfigSource::locate(args.config.as_deref(), "xdiff")?;
+    let config = DiffConfig::load(
+        &source,
+        args.config_format.unwrap_or(source.format()),
+        &args.vars,
+    )
+    .await?;
+    let mut names: Vec<_> = config.profiles.keys().collect();
+    names.sort();
+
+    let output = match args.output {
+        _ if args.names => names.iter().map(|name| format!("{}\n", name)).collect(),
+        PrintFormat::Text => {
+            let width = names
+                .iter()
+                .map(|name| name.len())
+                .max()
+                .unwrap_or_default();
+            let mut output = String::new();
+            for name in names {
+                let profile = &config.profiles[name];
+                writeln!(output, "{:width$}  {}", name, profile.req1.request_line()?)?;
+                writeln!(output, "{:width$}  {}", "", profile.req2.request_line()?)?;
+            }
+            output
+        }
+        PrintFormat::Json => {
+            let profiles = names
+                .into_iter()
+                .map(|name| {
+                    let profile = &config.profiles[name];
+                    Ok(json!({
+                        "name": name,
+                        "req1": profile.req1.summary()?,
+                        "req2": profile.req2.summary()?,
+                    }))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            format!("{}\n", serde_json::to_string_pretty(&profiles)?)
+        }
+    };
+
+    write_output(None, &output).await?;
+    Ok(ExitStatus::Ok)
+}
+
+async fn show(args: ShowArgs) -> Result<ExitStatus> {
+    let source = ConfigSource::locate(args.config.as_deref(), "xdiff")?;
+    let config = DiffConfig::load(
+        &source,
+        args.config_format.unwrap_or(source.format()),
+        &args.vars,
+    )
+    .await?;
+    let profile = config.get_profile(&args.profile).ok_or_else(|| {
+        InvalidConfig(anyhow::anyhow!(
+            "Profile {} not found in config file {}",
+            args.profile,
+            source
+        ))
+    })?;
+
+    let profile = profile.resolve(&args.extra_params.into())?.masked();
+    let config = DiffConfig::new(vec![(args.profile, profile)].into_iter().collect());
+    write_output(None, &args.output.to_string(&config)?).await?;
+    Ok(ExitStatus::Ok)
 }
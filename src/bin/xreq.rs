@@ -1,46 +1,77 @@
 use anyhow::Result;
 use clap::Parser;
 use dialoguer::{theme::ColorfulTheme, Input};
+use serde_json::json;
 use std::fmt::Write as _;
 use std::io::Write as _;
+use std::process::ExitCode;
 use xdiff_live::{
-    cli::{Action, Args, RunArgs},
-    get_body_text, get_headers_text, get_status_text, highlight_text, LoadConfig, RequestConfig,
-    RequestProfile, process_error_output,
+    cli::{
+        CompletionsArgs, ListArgs, ParseArgs, PrintFormat, ReqAction, ReqArgs, ReqRunArgs,
+        ShowArgs, ValidateArgs,
+    },
+    completions, get_body_text, get_headers_text, get_status_text, highlight_text,
+    process_error_output, prompt_vars, render_template, ConfigSource, ExitStatus, InvalidConfig,
+    LoadConfig, RequestConfig, RequestProfile,
 };
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    let args = Args::parse();
+async fn main() -> ExitCode {
+    let args = ReqArgs::parse();
     // println!("{:?}", args);
     let result = match args.action {
-        Action::Run(args) => run(args).await,
-        Action::Parse => parse().await,
+        ReqAction::Run(args) => run(args).await,
+        ReqAction::Parse(args) => parse(args).await,
+        ReqAction::List(args) => list(args).await,
+        ReqAction::Show(args) => show(args).await,
+        ReqAction::Validate(args) => validate(args).await,
+        ReqAction::Completions(args) => print_completions(args),
         _ => panic!("Not implemented"),
     };
-    
+
     process_error_output(result)
 }
 
-async fn run(args: RunArgs) -> Result<()> {
-    let config_file = args.config.unwrap_or_else(|| "./xreq_test.yml".to_string());
-    // println!("Using config file: {}", config_file);
-    let config = RequestConfig::load_yaml(&config_file).await?;
+async fn run(args: ReqRunArgs) -> Result<ExitStatus> {
+    let ReqRunArgs {
+        run: args,
+        check_status,
+    } = args;
+    let name = args.profile_name()?.to_string();
+    let source = ConfigSource::locate(args.config.as_deref(), "xreq")?;
+    // println!("Using config file: {}", source);
+    let config = RequestConfig::load(
+        &source,
+        args.config_format.unwrap_or(source.format()),
+        &args.vars,
+    )
+    .await?;
     // println!("{:?}", config);
-    let profile = config.get_profile(&args.profile).ok_or_else(|| {
-        anyhow::anyhow!(
+    let profile = config.get_profile(&name).ok_or_else(|| {
+        InvalidConfig(anyhow::anyhow!(
             "Profile {} not found in config file {}",
-            args.profile,
-            config_file
-        )
+            name,
+            source
+        ))
     })?;
 
+    let mut profile = profile.clone();
+    args.override_request(&mut profile);
     let extra_args = args.extra_params.into();
 
     let url = profile.get_url(&extra_args)?;
 
-    let res = profile.send(&extra_args).await?.into_inner();
+    let res = profile.send(&extra_args).await?;
+    if args.verbose {
+        let stderr = std::io::stderr();
+        let mut stderr = stderr.lock();
+        for retry in res.retries() {
+            writeln!(stderr, "{}", retry)?;
+        }
+    }
+    let res = res.into_inner();
 
+    let status_code = res.status();
     let status = get_status_text(&res)?;
     let headers = get_headers_text(&res, &[])?;
     let body = get_body_text(res, &[]).await?;
@@ -48,7 +79,7 @@ async fn run(args: RunArgs) -> Result<()> {
     let mut output = String::new();
 
     if atty::is(atty::Stream::Stdout) {
-        write!(&mut output, "Url: {}\n", url)?;
+        writeln!(&mut output, "Url: {}", url)?;
         write!(&mut output, "{}", status)?;
         write!(
             &mut output,
@@ -66,33 +97,140 @@ async fn run(args: RunArgs) -> Result<()> {
 
     writeln!(stdout, "{}", output)?;
 
-    Ok(())
+    match status_code {
+        s if check_status && s.is_client_error() => Ok(ExitStatus::ClientError),
+        s if check_status && s.is_server_error() => Ok(ExitStatus::ServerError),
+        _ => Ok(ExitStatus::Ok),
+    }
+}
+
+async fn list(args: ListArgs) -> Result<ExitStatus> {
+    let source = ConfigSource::locate(args.config.as_deref(), "xreq")?;
+    let config = RequestConfig::load(
+        &source,
+        args.config_format.unwrap_or(source.format()),
+        &args.vars,
+    )
+    .await?;
+    let mut names: Vec<_> = config.profiles.keys().collect();
+    names.sort();
+
+    let mut output = String::new();
+    match args.output {
+        _ if args.names => {
+            for name in names {
+                writeln!(output, "{}", name)?;
+            }
+        }
+        PrintFormat::Text => {
+            let width = names
+                .iter()
+                .map(|name| name.len())
+                .max()
+                .unwrap_or_default();
+            for name in names {
+                let profile = &config.profiles[name];
+                writeln!(output, "{:width$}  {}", name, profile.request_line()?)?;
+            }
+        }
+        PrintFormat::Json => {
+            let profiles = names
+                .into_iter()
+                .map(|name| {
+                    let mut summary = config.profiles[name].summary()?;
+                    summary["name"] = json!(name);
+                    Ok(summary)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            writeln!(output, "{}", serde_json::to_string_pretty(&profiles)?)?;
+        }
+    }
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    write!(stdout, "{}", output)?;
+    Ok(ExitStatus::Ok)
+}
+
+async fn show(args: ShowArgs) -> Result<ExitStatus> {
+    let source = ConfigSource::locate(args.config.as_deref(), "xreq")?;
+    let config = RequestConfig::load(
+        &source,
+        args.config_format.unwrap_or(source.format()),
+        &args.vars,
+    )
+    .await?;
+    let profile = config.get_profile(&args.profile).ok_or_else(|| {
+        InvalidConfig(anyhow::anyhow!(
+            "Profile {} not found in config file {}",
+            args.profile,
+            source
+        ))
+    })?;
+
+    let profile = profile.resolve(&args.extra_params.into())?.masked();
+    let config = RequestConfig::new(vec![(args.profile, profile)].into_iter().collect());
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    write!(stdout, "{}", args.output.to_string(&config)?)?;
+    Ok(ExitStatus::Ok)
+}
+
+/// check every profile and report all the errors, exit with 2 if any
+async fn validate(args: ValidateArgs) -> Result<ExitStatus> {
+    let source = ConfigSource::locate(args.config.as_deref(), "xreq")?;
+    let format = args.config_format.unwrap_or(source.format());
+    let check =
+        RequestConfig::check_config(&source.read().await?, source.path(), format, &args.vars);
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    write!(stdout, "{}", check.render(&source.to_string()))?;
+    if check.is_ok() {
+        Ok(ExitStatus::Ok)
+    } else {
+        Ok(ExitStatus::ConfigError)
+    }
+}
+
+fn print_completions(args: CompletionsArgs) -> Result<ExitStatus> {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    write!(
+        stdout,
+        "{}",
+        completions::<ReqArgs>(args.shell, "xreq-live")?
+    )?;
+    Ok(ExitStatus::Ok)
 }
 
-async fn parse() -> Result<()> {
+async fn parse(args: ParseArgs) -> Result<ExitStatus> {
     // println!("Parse not implemented");
     let theme = ColorfulTheme::default();
     let url = Input::<String>::with_theme(&theme)
         .with_prompt("Url1")
         .interact_text()?;
 
-    let profile: RequestProfile = url.parse()?;
+    let vars = prompt_vars(&theme, &[&url])?;
+    let profile: RequestProfile = render_template(&url, &vars)?.parse()?;
 
     let name = Input::<String>::with_theme(&theme)
         .with_prompt("Profile")
         .interact_text()?;
 
-    let config = RequestConfig::new(vec![(name, profile)].into_iter().collect());
-    let result = serde_yaml::to_string(&config)?;
+    // credentials of the url are not printed
+    profile.warn_masked_credentials();
+    let config = RequestConfig::new(vec![(name, profile.masked())].into_iter().collect());
+    let result = args.format.to_string(&config)?;
 
     let stdout = std::io::stdout();
     let mut stdout = stdout.lock();
     if atty::is(atty::Stream::Stdout) {
-        write!(stdout, "---\n{}", highlight_text(&result, "yaml", None)?)?;
+        let extension = args.format.extension();
+        write!(stdout, "{}", highlight_text(&result, extension, None)?)?;
     } else {
-        write!(stdout, "---\n{}", result)?;
+        write!(stdout, "{}", result)?;
     }
-    Ok(())
+    Ok(ExitStatus::Ok)
 }
-
-
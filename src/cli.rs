@@ -1,9 +1,13 @@
 // clap是一个简单易用，功能强大的命令行参数解析库。
 // clap允许多种方式指定我们的命令行。支持常规的Rust方法调用、宏或者YAML配置。
 use anyhow::{anyhow, Result};
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::time::Duration;
 
-use crate::ExtraArgs;
+use crate::{
+    highlight_text, ConfigFormat, DiffMode, ExtraArgs, Layout, RequestProfile, RetryPolicy,
+};
 
 /// Diff two http requests and compare the difference of the responses.
 #[derive(Parser, Debug, Clone)]
@@ -22,27 +26,243 @@ pub struct Args {
 pub enum Action {
     // #[clap(about = "Diff two http requests and compare the responses.")]
     /// Diff two API responses based on given profile
-    Run(RunArgs),
+    Run(DiffRunArgs),
     /// Parse URls to generate a profile
-    Parse,
+    Parse(ParseArgs),
+    /// List the profiles of the config with their method and url
+    List(ListArgs),
+    /// Show a profile with the extra args applied
+    Show(ShowArgs),
+    /// Validate every profile of the config without sending any request
+    Validate(ValidateArgs),
+    /// Generate shell completions, with profile names completed from the config
+    Completions(CompletionsArgs),
+}
+
+/// Send a http request based on given profile and print the response.
+#[derive(Parser, Debug, Clone)]
+#[clap(version, author, about, long_about = None)]
+pub struct ReqArgs {
+    #[clap(subcommand)]
+    pub action: ReqAction,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+#[non_exhaustive]
+pub enum ReqAction {
+    /// Send the request of the given profile and print the response
+    Run(ReqRunArgs),
+    /// Parse a URL to generate a profile
+    Parse(ParseArgs),
+    /// List the profiles of the config with their method and url
+    List(ListArgs),
+    /// Show a profile with the extra args applied
+    Show(ShowArgs),
+    /// Validate every profile of the config without sending any request
+    Validate(ValidateArgs),
+    /// Generate shell completions, with profile names completed from the config
+    Completions(CompletionsArgs),
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct DiffRunArgs {
+    #[clap(flatten)]
+    pub run: RunArgs,
+
+    /// How to compare the response bodies, overrides `diff_mode` in the profile
+    #[clap(long, value_enum)]
+    pub diff_mode: Option<DiffMode>,
+
+    /// Layout of the line diff, overrides `layout` in the profile
+    #[clap(long, value_enum)]
+    pub layout: Option<Layout>,
+
+    /// Output format of the diff
+    #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    /// Write the output to this file instead of stdout
+    #[clap(long, value_parser)]
+    pub out: Option<String>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ReqRunArgs {
+    #[clap(flatten)]
+    pub run: RunArgs,
+
+    /// Exit with 4 for a 4xx response and 5 for a 5xx response
+    #[clap(long)]
+    pub check_status: bool,
 }
 
 #[derive(Parser, Debug, Clone)]
 pub struct RunArgs {
+    /// Profile Name. Required unless `--output junit` is used, which runs every profile
+    /// when it is omitted
+    #[clap(short, long, value_parser)]
+    pub profile: Option<String>,
+
+    /// Extra args: `key=value` for a query param, `%key=value` for a header, `@key=value` for a body field
+    ///
+    /// Overrides the query, headers, and body of the request, e.g.
+    /// `-e page=2 -e %authorization=token -e @name=foo`
+    #[clap(short, long, value_parser = parse_key_val, number_of_values = 1)]
+    pub extra_params: Vec<KeyVal>,
+    
+    /// Template variables, fill in `{{name}}` placeholders with `--var name=value`.
+    /// Overrides the `vars` section of the config
+    #[clap(long = "var", value_parser = parse_var, number_of_values = 1)]
+    pub vars: Vec<(String, String)>,
+
+    /// Configuration to use
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// Format of the configuration, detected from the file extension if not given
+    #[clap(long, value_enum)]
+    pub config_format: Option<ConfigFormat>,
+
+    /// Timeout of each request, e.g. `10s`, overrides `timeout` in the profile
+    #[clap(long, value_parser = humantime::parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// Timeout of connecting to the server, overrides `connect_timeout` in the profile
+    #[clap(long, value_parser = humantime::parse_duration)]
+    pub connect_timeout: Option<Duration>,
+
+    /// Attempts of each request including the first one, overrides `retry.max_attempts`
+    /// in the profile
+    #[clap(long)]
+    pub max_attempts: Option<u32>,
+
+    /// Print the retried attempts of each request to stderr
+    #[clap(short, long)]
+    pub verbose: bool,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ListArgs {
+    /// Template variables, fill in `{{name}}` placeholders with `--var name=value`
+    #[clap(long = "var", value_parser = parse_var, number_of_values = 1)]
+    pub vars: Vec<(String, String)>,
+
+    /// Configuration to use
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// Format of the configuration, detected from the file extension if not given
+    #[clap(long, value_enum)]
+    pub config_format: Option<ConfigFormat>,
+
+    /// Output format
+    #[clap(short, long, value_enum, default_value_t = PrintFormat::Text)]
+    pub output: PrintFormat,
+
+    /// Print only the profile names, one per line
+    #[clap(long, conflicts_with = "output")]
+    pub names: bool,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ShowArgs {
     /// Profile Name
     #[clap(short, long, value_parser)]
     pub profile: String,
 
-    /// Overrides Args. Could be used to override the query, headers, and body of the request
-    /// for query params, use `-e key=value`
-    /// for headers, use `-e %key=value`
-    /// for body, use `-e @key=value`
+    /// Extra args: `key=value` for a query param, `%key=value` for a header, `@key=value` for a body field
     #[clap(short, long, value_parser = parse_key_val, number_of_values = 1)]
     pub extra_params: Vec<KeyVal>,
-    
+
+    /// Template variables, fill in `{{name}}` placeholders with `--var name=value`
+    #[clap(long = "var", value_parser = parse_var, number_of_values = 1)]
+    pub vars: Vec<(String, String)>,
+
+    /// Configuration to use
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// Format of the configuration, detected from the file extension if not given
+    #[clap(long, value_enum)]
+    pub config_format: Option<ConfigFormat>,
+
+    /// Output format
+    #[clap(short, long, value_enum, default_value_t = PrintFormat::Text)]
+    pub output: PrintFormat,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ValidateArgs {
+    /// Template variables, fill in `{{name}}` placeholders with `--var name=value`
+    #[clap(long = "var", value_parser = parse_var, number_of_values = 1)]
+    pub vars: Vec<(String, String)>,
+
     /// Configuration to use
     #[clap(short, long, value_parser)]
     pub config: Option<String>,
+
+    /// Format of the configuration, detected from the file extension if not given
+    #[clap(long, value_enum)]
+    pub config_format: Option<ConfigFormat>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ParseArgs {
+    /// Format of the generated config
+    #[clap(short, long, value_enum, default_value_t = ConfigFormat::Yaml)]
+    pub format: ConfigFormat,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct CompletionsArgs {
+    /// Shell to generate the completions for
+    #[clap(value_enum)]
+    pub shell: Shell,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Output format of `list` and `show`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PrintFormat {
+    /// human readable, highlighted on a terminal
+    Text,
+    /// json for scripting
+    Json,
+}
+
+impl PrintFormat {
+    /// serialize the config for `show`, yaml is highlighted on a terminal
+    pub fn to_string<T: Serialize>(&self, config: &T) -> Result<String> {
+        Ok(match self {
+            Self::Text => {
+                let result = serde_yaml::to_string(config)?;
+                if atty::is(atty::Stream::Stdout) {
+                    format!("---\n{}", highlight_text(&result, "yaml", None)?)
+                } else {
+                    format!("---\n{}", result)
+                }
+            }
+            Self::Json => format!("{}\n", serde_json::to_string_pretty(config)?),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// colored line diff for the terminal
+    Text,
+    /// machine-readable diff report
+    Json,
+    /// self-contained html report
+    Html,
+    /// junit xml report with one test case per profile, for CI
+    Junit,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -85,6 +305,42 @@ pub fn parse_key_val(s: &str) -> Result<KeyVal> {
     })
 }
 
+pub fn parse_var(s: &str) -> Result<(String, String)> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Invalid var: {}, expect name=value", s))?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(anyhow!("Invalid var: {}, name is empty", s));
+    }
+
+    Ok((name.to_string(), value.trim().to_string()))
+}
+
+impl RunArgs {
+    /// name of the profile to run, errors if `-p` is not given
+    pub fn profile_name(&self) -> Result<&str> {
+        self.profile
+            .as_deref()
+            .ok_or_else(|| anyhow!("Profile is required, use -p <PROFILE>"))
+    }
+
+    /// apply the timeouts and retry attempts given on the command line to the request
+    pub fn override_request(&self, req: &mut RequestProfile) {
+        if let Some(timeout) = self.timeout {
+            req.timeout = Some(timeout);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            req.connect_timeout = Some(connect_timeout);
+        }
+        if let Some(max_attempts) = self.max_attempts {
+            req.retry
+                .get_or_insert_with(RetryPolicy::default)
+                .max_attempts = max_attempts;
+        }
+    }
+}
+
 impl From<Vec<KeyVal>> for ExtraArgs {
     fn from(args: Vec<KeyVal>) -> Self {
         let mut headers = vec![];
@@ -156,7 +412,7 @@ mod tests {
 
         let key_vals = args
             .into_iter()
-            .map(|arg| parse_key_val(arg))
+            .map(parse_key_val)
             .collect::<Result<Vec<_>>>()
             .unwrap();
 
@@ -187,4 +443,14 @@ mod tests {
 
         );
     }
+
+    #[test]
+    fn parse_var_should_work() {
+        assert_eq!(
+            parse_var("id = 1=2").unwrap(),
+            ("id".to_string(), "1=2".to_string())
+        );
+        assert!(parse_var("id").is_err());
+        assert!(parse_var("=1").is_err());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,113 @@
+use crate::cli::Shell;
+use anyhow::Result;
+use clap::CommandFactory;
+
+// The scripts generated by clap_complete only know the static arguments. These snippets
+// complete `-p/--profile` of `run` and `show` by calling `BIN list --names`, passing on
+// `-c/--config` if it is on the command line. `BIN` is replaced by the binary name.
+
+const BASH_PROFILES: &str = r#"
+_BIN_profiles() {
+    local i config
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            -c|--config) config="${COMP_WORDS[i+1]}" ;;
+        esac
+    done
+    BIN list --names ${config:+-c "$config"} 2>/dev/null
+}
+
+_BIN_with_profiles() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    if [[ "$prev" == "-p" || "$prev" == "--profile" ]]; then
+        COMPREPLY=( $(compgen -W "$(_BIN_profiles)" -- "$cur") )
+        return 0
+    fi
+    _BIN "$@"
+}
+
+complete -F _BIN_with_profiles -o bashdefault -o default BIN
+"#;
+
+const ZSH_PROFILES: &str = r#"
+(( $+functions[_BIN_profiles] )) ||
+_BIN_profiles() {
+    local i=${words[(I)(-c|--config)]}
+    local -a args profiles
+    (( i )) && args=(-c "${words[i+1]}")
+    profiles=(${(f)"$(BIN list --names $args 2>/dev/null)"})
+    _describe -t profiles 'profile' profiles
+}
+
+_BIN_with_profiles() {
+    if [[ ${words[CURRENT-1]} == (-p|--profile) ]]; then
+        _BIN_profiles
+    else
+        _BIN "$@"
+    fi
+}
+
+# when autoloaded from fpath, the script above has just completed the first call
+if [ "$funcstack[1]" = "_BIN" ] && [[ ${words[CURRENT-1]} == (-p|--profile) ]]; then
+    _BIN_profiles
+fi
+compdef _BIN_with_profiles BIN
+"#;
+
+const FISH_PROFILES: &str = r#"
+function __BIN_profiles
+    set -l tokens (commandline -opc)
+    set -l i (contains -i -- -c $tokens; or contains -i -- --config $tokens)
+    if test -n "$i"
+        BIN list --names -c $tokens[(math $i + 1)] 2>/dev/null
+    else
+        BIN list --names 2>/dev/null
+    end
+end
+complete -c BIN -n "__fish_seen_subcommand_from run show" -s p -l profile -f -a "(__BIN_profiles)"
+"#;
+
+/// Generate the completion script of `bin`, whose arguments are `T`, for the shell
+pub fn completions<T: CommandFactory>(shell: Shell, bin: &str) -> Result<String> {
+    let mut buf = vec![];
+    let generator = match shell {
+        Shell::Bash => clap_complete::Shell::Bash,
+        Shell::Zsh => clap_complete::Shell::Zsh,
+        Shell::Fish => clap_complete::Shell::Fish,
+    };
+    clap_complete::generate(generator, &mut T::command(), bin, &mut buf);
+    let script = String::from_utf8(buf)?;
+
+    let output = match shell {
+        Shell::Bash => script + &BASH_PROFILES.replace("BIN", bin),
+        Shell::Zsh => script + &ZSH_PROFILES.replace("BIN", bin),
+        Shell::Fish => script + &FISH_PROFILES.replace("BIN", bin),
+    };
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Args, ReqArgs};
+
+    #[test]
+    fn completions_should_complete_profiles() {
+        let bash = completions::<Args>(Shell::Bash, "xdiff-live").unwrap();
+        assert!(bash.contains("complete -F _xdiff-live_with_profiles"));
+        assert!(bash.contains("xdiff-live list --names ${config:+-c \"$config\"}"));
+
+        let zsh = completions::<Args>(Shell::Zsh, "xdiff-live").unwrap();
+        assert!(zsh.contains("_xdiff-live_profiles() {"));
+        assert!(zsh.ends_with("compdef _xdiff-live_with_profiles xdiff-live\n"));
+
+        let fish = completions::<ReqArgs>(Shell::Fish, "xreq-live").unwrap();
+        assert!(fish.contains("-s p -l profile -f -a \"(__xreq-live_profiles)\""));
+    }
+
+    #[test]
+    fn completions_should_document_extra_args() {
+        let fish = completions::<Args>(Shell::Fish, "xdiff-live").unwrap();
+        assert!(fish.contains("`%key=value` for a header"));
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    Request, RequestBuilder,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use url::{Position, Url};
+
+use super::{is_default, ValidateConfig};
+
+/// shown instead of an inline credential when a profile is displayed
+pub(crate) const MASK: &str = "***";
+
+/// Credentials of a request, applied when it is sent
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Auth {
+    /// `authorization: Basic base64(username:password)`
+    Basic { username: String, password: Secret },
+    /// `authorization: Bearer token`
+    Bearer { token: Secret },
+    /// the key sent as a header, or as a query param with `in: query`
+    ApiKey {
+        name: String,
+        value: Secret,
+        #[serde(rename = "in", skip_serializing_if = "is_default", default)]
+        location: ApiKeyLocation,
+    },
+    /// answers the digest challenge of a `401` response, so the request is sent twice
+    Digest { username: String, password: Secret },
+}
+
+/// Where an api key is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyLocation {
+    #[default]
+    Header,
+    Query,
+}
+
+/// A credential given inline, or read from an env var (`{ env: NAME }`) or a file
+/// (`{ file: path }`) when the request is sent. Inline values are serialized as is, use
+/// `masked` before displaying them
+#[derive(Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Secret {
+    Env { env: String },
+    File { file: PathBuf },
+    Value(String),
+}
+
+impl Auth {
+    /// add the credentials to the request; digest auth needs a challenge, see `digest`
+    pub(crate) fn apply(&self, req: RequestBuilder) -> Result<RequestBuilder> {
+        Ok(match self {
+            Self::Basic { username, password } => {
+                req.basic_auth(username, Some(password.reveal()?))
+            }
+            Self::Bearer { token } => req.bearer_auth(token.reveal()?),
+            Self::ApiKey {
+                name,
+                value,
+                location: ApiKeyLocation::Header,
+            } => req.header(
+                HeaderName::from_str(name)?,
+                HeaderValue::from_str(&value.reveal()?)?,
+            ),
+            Self::ApiKey {
+                name,
+                value,
+                location: ApiKeyLocation::Query,
+            } => req.query(&[(name, value.reveal()?)]),
+            Self::Digest { .. } => req,
+        })
+    }
+
+    /// the `authorization` header answering the `www-authenticate` challenge, none if this is
+    /// not digest auth
+    pub(crate) fn digest(&self, req: &Request, challenge: &str) -> Result<Option<HeaderValue>> {
+        let (username, password) = match self {
+            Self::Digest { username, password } => (username, password.reveal()?),
+            _ => return Ok(None),
+        };
+        let mut prompt = digest_auth::parse(challenge)
+            .with_context(|| format!("invalid digest challenge: {}", challenge))?;
+        let context = digest_auth::AuthContext::new_with_method(
+            username.as_str(),
+            password,
+            &req.url()[Position::BeforePath..],
+            req.body().and_then(|body| body.as_bytes()),
+            req.method().as_str().into(),
+        );
+        let answer = prompt.respond(&context)?;
+        Ok(Some(HeaderValue::from_str(&answer.to_header_string())?))
+    }
+}
+
+impl Auth {
+    /// replace an api key sent as a query param by `***` in the url, e.g. the url of a
+    /// reqwest error, which shows the url the request was sent to
+    pub(crate) fn mask_url(&self, url: &mut Url) {
+        if let Self::ApiKey {
+            name,
+            location: ApiKeyLocation::Query,
+            ..
+        } = self
+        {
+            let pairs: Vec<(String, String)> = url
+                .query_pairs()
+                .map(|(k, v)| {
+                    let v = if k == name.as_str() { MASK.into() } else { v };
+                    (k.into_owned(), v.into_owned())
+                })
+                .collect();
+            url.query_pairs_mut().clear().extend_pairs(pairs);
+        }
+    }
+
+    /// the auth with inline credentials replaced by `***`, for displaying it
+    pub fn masked(&self) -> Self {
+        match self {
+            Self::Basic { username, password } => Self::Basic {
+                username: username.clone(),
+                password: password.masked(),
+            },
+            Self::Bearer { token } => Self::Bearer {
+                token: token.masked(),
+            },
+            Self::ApiKey {
+                name,
+                value,
+                location,
+            } => Self::ApiKey {
+                name: name.clone(),
+                value: value.masked(),
+                location: *location,
+            },
+            Self::Digest { username, password } => Self::Digest {
+                username: username.clone(),
+                password: password.masked(),
+            },
+        }
+    }
+}
+
+impl ValidateConfig for Auth {
+    fn validate(&self) -> Result<()> {
+        if let Self::ApiKey { name, location, .. } = self {
+            if name.is_empty() {
+                return Err(anyhow!("api key name must not be empty"));
+            }
+            if *location == ApiKeyLocation::Header {
+                HeaderName::from_str(name)
+                    .with_context(|| format!("invalid api key header name: {}", name))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Secret {
+    /// the value of the credential
+    pub fn reveal(&self) -> Result<String> {
+        match self {
+            Self::Value(value) => Ok(value.clone()),
+            Self::Env { env } => {
+                std::env::var(env).with_context(|| format!("credential env var {} is not set", env))
+            }
+            Self::File { file } => {
+                let content = std::fs::read_to_string(file).with_context(|| {
+                    format!("failed to read credential file {}", file.display())
+                })?;
+                Ok(content.trim_end_matches(['\r', '\n']).to_string())
+            }
+        }
+    }
+
+    /// an inline value is replaced by `***`, env vars and files only name the credential
+    pub fn masked(&self) -> Self {
+        match self {
+            Self::Value(_) => Self::Value(MASK.to_string()),
+            secret => secret.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Env { env } => f.debug_struct("Env").field("env", env).finish(),
+            Self::File { file } => f.debug_struct("File").field("file", file).finish(),
+            Self::Value(_) => f.debug_tuple("Value").field(&MASK).finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auth_should_mask_inline_secrets() {
+        let yaml = "type: api_key\nname: x-api-key\nvalue: abcd\nin: query\n";
+        let auth: Auth = serde_yaml::from_str(yaml).unwrap();
+        // the value is kept when serialized, so a written config can be loaded again
+        assert_eq!(serde_yaml::to_string(&auth).unwrap(), yaml);
+        assert_eq!(
+            serde_yaml::to_string(&auth.masked()).unwrap(),
+            "type: api_key\nname: x-api-key\nvalue: '***'\nin: query\n"
+        );
+        assert!(!format!("{:?}", auth).contains("abcd"));
+
+        let auth: Auth = serde_yaml::from_str("type: bearer\ntoken:\n  env: TOKEN\n").unwrap();
+        assert_eq!(
+            serde_yaml::to_string(&auth.masked()).unwrap(),
+            "type: bearer\ntoken:\n  env: TOKEN\n"
+        );
+    }
+
+    #[test]
+    fn secret_reveal_should_read_files() {
+        let path = std::env::temp_dir().join("xdiff-secret-test");
+        std::fs::write(&path, "abcd\n").unwrap();
+        let secret = Secret::File { file: path };
+        assert_eq!(secret.reveal().unwrap(), "abcd");
+
+        let secret = Secret::Env {
+            env: "XDIFF_TEST_NOT_SET".into(),
+        };
+        assert_eq!(
+            secret.reveal().unwrap_err().to_string(),
+            "credential env var XDIFF_TEST_NOT_SET is not set"
+        );
+    }
+}
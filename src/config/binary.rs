@@ -0,0 +1,182 @@
+use encoding_rs::{Encoding, UTF_8};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+use super::BodyFormat;
+
+/// media types whose bodies are binary, in addition to `image/*`, `audio/*`, `video/*` and
+/// `font/*`
+const BINARY_TYPES: &[&str] = &[
+    "application/pdf",
+    "application/zip",
+    "application/gzip",
+    "application/x-gzip",
+    "application/x-tar",
+    "application/x-7z-compressed",
+    "application/x-bzip2",
+    "application/wasm",
+    "application/msword",
+    "application/vnd.ms-excel",
+];
+
+const BINARY_PREFIXES: &[&str] = &[
+    "image/",
+    "audio/",
+    "video/",
+    "font/",
+    "application/vnd.openxmlformats-",
+];
+
+/// bytes shown on each line of a hex dump
+const HEX_WIDTH: usize = 16;
+/// lines of the hex dump around the first difference
+const HEX_LINES: usize = 4;
+
+/// whether the body is binary, by its content type or, if that is unknown, its content.
+/// `application/octet-stream` is often used for text as well, so it is sniffed
+pub(crate) fn is_binary(content_type: Option<&str>, bytes: &[u8]) -> bool {
+    let content_type = content_type.unwrap_or_default().to_ascii_lowercase();
+    let mut params = content_type.split(';');
+    let content_type = params.next().unwrap_or_default().trim();
+    let charset = params.any(|param| param.trim().starts_with("charset="));
+    if charset
+        || content_type.starts_with("text/")
+        || BodyFormat::from_content_type(content_type).is_some()
+    {
+        return false;
+    }
+    if BINARY_TYPES.contains(&content_type)
+        || BINARY_PREFIXES
+            .iter()
+            .any(|prefix| content_type.starts_with(prefix))
+    {
+        return true;
+    }
+    bytes.contains(&0) || std::str::from_utf8(bytes).is_err()
+}
+
+/// size, SHA-256 and, for images, format and dimensions of a binary body, diffed instead of
+/// its content
+pub(crate) fn summary(bytes: &[u8]) -> String {
+    let mut output = format!("binary body: {} bytes\n", bytes.len());
+    let _ = write!(output, "sha256: {:x}", Sha256::digest(bytes));
+    if let (Ok(kind), Ok(size)) = (imagesize::image_type(bytes), imagesize::blob_size(bytes)) {
+        let kind = format!("{:?}", kind).to_lowercase();
+        let _ = write!(output, "\nimage: {} {}x{}", kind, size.width, size.height);
+    }
+    output
+}
+
+/// hex dump of both bodies around the first byte where they differ, none if they are equal
+pub(crate) fn hex_diff(bytes1: &[u8], bytes2: &[u8]) -> Option<String> {
+    let offset = bytes1
+        .iter()
+        .zip(bytes2)
+        .position(|(b1, b2)| b1 != b2)
+        .or_else(|| (bytes1.len() != bytes2.len()).then(|| bytes1.len().min(bytes2.len())))?;
+
+    let start = offset / HEX_WIDTH * HEX_WIDTH;
+    let mut output = format!("first difference at byte {} (0x{:x})\n", offset, offset);
+    for (name, bytes) in [("req1", bytes1), ("req2", bytes2)] {
+        let _ = writeln!(output, "{}:", name);
+        let end = bytes.len().min(start + HEX_WIDTH * HEX_LINES);
+        if start >= end {
+            output.push_str("(end of body)\n");
+            continue;
+        }
+        for (i, line) in bytes[start..end].chunks(HEX_WIDTH).enumerate() {
+            output.push_str(&hex_line(start + i * HEX_WIDTH, line));
+        }
+    }
+    Some(output)
+}
+
+/// the body decoded with the charset of the content type, utf-8 by default
+pub(crate) fn decode(content_type: Option<&str>, bytes: &[u8]) -> String {
+    let encoding = content_type
+        .and_then(|v| {
+            v.split(';')
+                .find_map(|param| param.trim().strip_prefix("charset="))
+        })
+        .and_then(|charset| Encoding::for_label(charset.trim_matches('"').as_bytes()))
+        .unwrap_or(UTF_8);
+    encoding.decode(bytes).0.into_owned()
+}
+
+/// `00000010  89 50 4e 47 ...  |.PNG...|`
+fn hex_line(offset: usize, bytes: &[u8]) -> String {
+    let hex: Vec<_> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    let ascii: String = bytes
+        .iter()
+        .map(|b| match b {
+            0x20..=0x7e => *b as char,
+            _ => '.',
+        })
+        .collect();
+    format!(
+        "{:08x}  {:<width$}  |{}|\n",
+        offset,
+        hex.join(" "),
+        ascii,
+        width = HEX_WIDTH * 3 - 1
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// header of a 2x3 png
+    const PNG: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03, 0x08, 0x06, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn is_binary_should_check_content_type_then_content() {
+        assert!(is_binary(Some("image/png"), b"abc"));
+        assert!(is_binary(Some("application/pdf"), b"abc"));
+        assert!(!is_binary(Some("application/problem+json"), PNG));
+        assert!(is_binary(Some("application/octet-stream"), PNG));
+        assert!(!is_binary(Some("application/octet-stream"), b"abc"));
+        assert!(!is_binary(
+            Some("application/x-thing; charset=latin1"),
+            b"\xe9"
+        ));
+        assert!(!is_binary(None, "héllo".as_bytes()));
+    }
+
+    #[test]
+    fn summary_should_report_image_dimensions() {
+        assert_eq!(
+            summary(PNG),
+            format!(
+                "binary body: 29 bytes\nsha256: {:x}\nimage: png 2x3",
+                Sha256::digest(PNG)
+            )
+        );
+        assert!(!summary(b"\x00\x01").contains("image"));
+    }
+
+    #[test]
+    fn hex_diff_should_dump_first_difference() {
+        let mut other = PNG.to_vec();
+        other[19] = 0x04;
+        assert_eq!(
+            hex_diff(PNG, &other).unwrap(),
+            [
+                "first difference at byte 19 (0x13)",
+                "req1:",
+                "00000010  00 00 00 02 00 00 00 03 08 06 00 00 00           |.............|",
+                "req2:",
+                "00000010  00 00 00 04 00 00 00 03 08 06 00 00 00           |.............|",
+                "",
+            ]
+            .join("\n")
+        );
+        assert!(hex_diff(PNG, PNG).is_none());
+        assert!(hex_diff(&PNG[..16], PNG)
+            .unwrap()
+            .contains("req1:\n(end of body)\n"));
+    }
+}
@@ -0,0 +1,448 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+use super::mask::{mask_json, mask_text, warn_path_masks, Mask};
+use crate::{print_warning, JsonPath};
+
+/// How a response body is normalized before it is diffed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyFormat {
+    /// pretty-printed with sorted keys, `skip_body` paths are removed
+    Json,
+    /// one tag per line, indented, with sorted attributes
+    Xml,
+    /// like xml, void elements such as `<br>` need no closing tag
+    Html,
+    /// re-emitted with sorted keys, `skip_body` paths are removed
+    Yaml,
+    /// kept as is
+    Text,
+}
+
+/// media types and the format of their bodies, `+json` style suffixes are matched as well
+const MEDIA_TYPES: &[(&str, BodyFormat)] = &[
+    ("application/json", BodyFormat::Json),
+    ("text/json", BodyFormat::Json),
+    ("application/xml", BodyFormat::Xml),
+    ("text/xml", BodyFormat::Xml),
+    ("text/html", BodyFormat::Html),
+    ("application/xhtml+xml", BodyFormat::Html),
+    ("application/yaml", BodyFormat::Yaml),
+    ("application/x-yaml", BodyFormat::Yaml),
+    ("text/yaml", BodyFormat::Yaml),
+    ("text/x-yaml", BodyFormat::Yaml),
+];
+
+const SUFFIXES: &[(&str, BodyFormat)] = &[
+    ("+json", BodyFormat::Json),
+    ("+xml", BodyFormat::Xml),
+    ("+yaml", BodyFormat::Yaml),
+];
+
+/// html elements which never have a closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+impl BodyFormat {
+    /// the format of a media type, none if it is not known
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let content_type = content_type.trim().to_ascii_lowercase();
+        MEDIA_TYPES
+            .iter()
+            .find(|(media_type, _)| *media_type == content_type)
+            .or_else(|| {
+                SUFFIXES
+                    .iter()
+                    .find(|(suffix, _)| content_type.ends_with(suffix))
+            })
+            .map(|(_, format)| *format)
+    }
+
+    /// guess the format from the body itself
+    pub fn sniff(text: &str) -> Self {
+        let text = text.trim_start();
+        let head = text
+            .get(..text.len().min(64))
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if (text.starts_with('{') || text.starts_with('['))
+            && serde_json::from_str::<serde_json::Value>(text).is_ok()
+        {
+            Self::Json
+        } else if head.starts_with("<!doctype html") || head.starts_with("<html") {
+            Self::Html
+        } else if text.starts_with('<') {
+            Self::Xml
+        } else {
+            Self::Text
+        }
+    }
+
+    /// normalize the body so that equal documents give equal text
+    pub fn normalize(&self, text: &str, skip_body: &[String]) -> Result<String> {
+        self.normalize_masked(text, skip_body, &[])
+    }
+
+    /// normalize the body, then hide the parts matching the masks
+    pub fn normalize_masked(
+        &self,
+        text: &str,
+        skip_body: &[String],
+        masks: &[Mask],
+    ) -> Result<String> {
+        let mut warnings = vec![];
+        let text = self.normalize_warned(text, skip_body, masks, &mut warnings);
+        print_warnings(&warnings);
+        text
+    }
+
+    /// like `normalize_masked`, paths which are not found are added to the warnings
+    fn normalize_warned(
+        &self,
+        text: &str,
+        skip_body: &[String],
+        masks: &[Mask],
+        warnings: &mut Vec<String>,
+    ) -> Result<String> {
+        let text = match self {
+            Self::Json => {
+                let json = serde_json::from_str(text)?;
+                let json = filter_json(json, skip_body, masks, warnings)?;
+                serde_json::to_string_pretty(&json)?
+            }
+            Self::Yaml => {
+                // converted to json first, so the keys are sorted
+                let json = serde_yaml::from_str(text)?;
+                let json = filter_json(json, skip_body, masks, warnings)?;
+                serde_yaml::to_string(&json)?.trim_end().to_string()
+            }
+            Self::Xml => normalize_markup(text, false)?,
+            Self::Html => normalize_markup(text, true)?,
+            Self::Text => text.to_string(),
+        };
+        Ok(match self {
+            // already masked by `filter_json`
+            Self::Json | Self::Yaml => text,
+            _ => {
+                warn_path_masks(masks, &self.to_string(), warnings);
+                mask_text(masks, &text)
+            }
+        })
+    }
+
+    /// the extension used to highlight bodies of the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Xml => "xml",
+            Self::Html => "html",
+            Self::Yaml => "yaml",
+            Self::Text => "txt",
+        }
+    }
+}
+
+/// normalize the body in the forced format, or the one of its content type. A body whose
+/// format is only sniffed is kept as is if it can not be parsed. Paths which are not found
+/// are added to the warnings, so that the caller reports them once for both responses
+pub(crate) fn normalize_body(
+    forced: Option<BodyFormat>,
+    content_type: Option<&str>,
+    text: &str,
+    skip_body: &[String],
+    masks: &[Mask],
+    warnings: &mut Vec<String>,
+) -> Result<String> {
+    let declared = forced.or_else(|| content_type.and_then(BodyFormat::from_content_type));
+    match declared {
+        Some(format) => format
+            .normalize_warned(text, skip_body, masks, warnings)
+            .with_context(|| format!("failed to parse {} body", format)),
+        None => {
+            let mut sniffed = vec![];
+            match BodyFormat::sniff(text).normalize_warned(text, skip_body, masks, &mut sniffed) {
+                Ok(body) => {
+                    warnings.append(&mut sniffed);
+                    Ok(body)
+                }
+                Err(_) => {
+                    warn_path_masks(masks, &BodyFormat::Text.to_string(), warnings);
+                    Ok(mask_text(masks, text))
+                }
+            }
+        }
+    }
+}
+
+/// print each warning once
+pub(crate) fn print_warnings(warnings: &[String]) {
+    let mut printed = HashSet::new();
+    for warning in warnings {
+        if printed.insert(warning) {
+            print_warning(warning);
+        }
+    }
+}
+
+// each entry is a json path, e.g. `id`, `data.meta.request_id`, `items[*].updated_at`
+// or `/data/meta/request_id`; paths that match nothing are added to the warnings
+fn filter_json(
+    mut json: serde_json::Value,
+    skip: &[String],
+    masks: &[Mask],
+    warnings: &mut Vec<String>,
+) -> Result<serde_json::Value> {
+    for path in skip {
+        let json_path: JsonPath = path.parse()?;
+        if json_path.remove(&mut json) == 0 {
+            warnings.push(format!(
+                "skip_body path `{}` not found in response body",
+                path
+            ));
+        }
+    }
+    mask_json(masks, &mut json, warnings)?;
+    Ok(json)
+}
+
+/// A line of normalized markup
+enum Token {
+    Open(String),
+    Close(String),
+    /// a self-closing tag, text, comment or declaration
+    Leaf(String),
+}
+
+fn normalize_markup(text: &str, html: bool) -> Result<String> {
+    let mut reader = Reader::from_str(text);
+    let config = reader.config_mut();
+    config.trim_text(true);
+    // html leaves many tags unclosed
+    config.check_end_names = !html;
+
+    let mut tokens = vec![];
+    loop {
+        let token = match reader.read_event()? {
+            Event::Start(tag) => {
+                let tag_text = start_tag(&tag, html)?;
+                if html && is_void(&tag) {
+                    Token::Leaf(format!("<{}>", tag_text))
+                } else {
+                    Token::Open(format!("<{}>", tag_text))
+                }
+            }
+            Event::Empty(tag) => Token::Leaf(format!("<{} />", start_tag(&tag, html)?)),
+            Event::End(tag) => {
+                let name = String::from_utf8_lossy(tag.name().as_ref()).to_string();
+                if html && VOID_ELEMENTS.contains(&name.to_ascii_lowercase().as_str()) {
+                    continue;
+                }
+                Token::Close(format!("</{}>", name))
+            }
+            Event::Text(text) => {
+                let text = String::from_utf8_lossy(&text);
+                // collapse runs of whitespace, which are not significant in markup
+                Token::Leaf(text.split_whitespace().collect::<Vec<_>>().join(" "))
+            }
+            Event::CData(data) => {
+                Token::Leaf(format!("<![CDATA[{}]]>", String::from_utf8_lossy(&data)))
+            }
+            Event::Comment(comment) => {
+                Token::Leaf(format!("<!--{}-->", String::from_utf8_lossy(&comment)))
+            }
+            Event::Decl(decl) => Token::Leaf(format!("<?{}?>", String::from_utf8_lossy(&decl))),
+            Event::PI(pi) => Token::Leaf(format!("<?{}?>", String::from_utf8_lossy(&pi))),
+            Event::DocType(doctype) => Token::Leaf(format!(
+                "<!DOCTYPE {}>",
+                String::from_utf8_lossy(&doctype).trim()
+            )),
+            Event::Eof => break,
+        };
+        tokens.push(token);
+    }
+
+    let mut output = vec![];
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < tokens.len() {
+        let indent = "  ".repeat(depth);
+        match &tokens[i..] {
+            // an element with only text is kept on one line
+            [Token::Open(open), Token::Leaf(text), Token::Close(close), ..]
+                if !text.starts_with('<') =>
+            {
+                output.push(format!("{}{}{}{}", indent, open, text, close));
+                i += 3;
+                continue;
+            }
+            [Token::Open(open), Token::Close(close), ..] => {
+                output.push(format!("{}{}{}", indent, open, close));
+                i += 2;
+                continue;
+            }
+            [Token::Open(open), ..] => {
+                output.push(format!("{}{}", indent, open));
+                depth += 1;
+            }
+            [Token::Close(close), ..] => {
+                depth = depth.saturating_sub(1);
+                output.push(format!("{}{}", "  ".repeat(depth), close));
+            }
+            [Token::Leaf(leaf), ..] => output.push(format!("{}{}", indent, leaf)),
+            [] => unreachable!(),
+        }
+        i += 1;
+    }
+    Ok(output.join("\n"))
+}
+
+/// the name of the tag with its attributes sorted by name
+fn start_tag(tag: &BytesStart, html: bool) -> Result<String> {
+    let attributes = if html {
+        tag.html_attributes()
+    } else {
+        tag.attributes()
+    };
+    let mut attrs = vec![];
+    for attr in attributes {
+        let attr = attr?;
+        attrs.push(format!(
+            "{}=\"{}\"",
+            String::from_utf8_lossy(attr.key.as_ref()),
+            String::from_utf8_lossy(&attr.value)
+        ));
+    }
+    attrs.sort();
+
+    let mut output = String::from_utf8_lossy(tag.name().as_ref()).to_string();
+    for attr in attrs {
+        output.push(' ');
+        output.push_str(&attr);
+    }
+    Ok(output)
+}
+
+fn is_void(tag: &BytesStart) -> bool {
+    let name = String::from_utf8_lossy(tag.name().as_ref()).to_ascii_lowercase();
+    VOID_ELEMENTS.contains(&name.as_str())
+}
+
+impl fmt::Display for BodyFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn body_format_should_be_chosen_by_content_type_or_content() {
+        assert_eq!(
+            BodyFormat::from_content_type("application/problem+json"),
+            Some(BodyFormat::Json)
+        );
+        assert_eq!(
+            BodyFormat::from_content_type("application/atom+xml"),
+            Some(BodyFormat::Xml)
+        );
+        assert_eq!(
+            BodyFormat::from_content_type("application/xhtml+xml"),
+            Some(BodyFormat::Html)
+        );
+        assert_eq!(BodyFormat::from_content_type("text/plain"), None);
+
+        assert_eq!(BodyFormat::sniff(" {\"a\": 1}"), BodyFormat::Json);
+        assert_eq!(BodyFormat::sniff("<!DOCTYPE html><p>"), BodyFormat::Html);
+        assert_eq!(BodyFormat::sniff("<a/>"), BodyFormat::Xml);
+        assert_eq!(BodyFormat::sniff("{not json"), BodyFormat::Text);
+    }
+
+    #[test]
+    fn normalize_should_canonicalize_markup() {
+        let xml = r#"<?xml version="1.0"?><todo id="1" done="false"><title>  a
+            todo </title><tags/></todo>"#;
+        assert_eq!(
+            BodyFormat::Xml.normalize(xml, &[]).unwrap(),
+            [
+                r#"<?xml version="1.0"?>"#,
+                r#"<todo done="false" id="1">"#,
+                "  <title>a todo</title>",
+                "  <tags />",
+                "</todo>",
+            ]
+            .join("\n")
+        );
+
+        let html = "<html><body class=main><p>hi<br>there</p></body></html>";
+        assert_eq!(
+            BodyFormat::Html.normalize(html, &[]).unwrap(),
+            [
+                "<html>",
+                "  <body class=\"main\">",
+                "    <p>",
+                "      hi",
+                "      <br>",
+                "      there",
+                "    </p>",
+                "  </body>",
+                "</html>",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn normalize_should_sort_yaml_and_skip_paths() {
+        let yaml = "b: 1\na:\n  id: 2\n  at: now\n";
+        assert_eq!(
+            BodyFormat::Yaml
+                .normalize(yaml, &["a.at".to_string()])
+                .unwrap(),
+            "a:\n  id: 2\nb: 1"
+        );
+        // a sniffed body is kept as is when it can not be parsed
+        assert_eq!(
+            normalize_body(None, Some("text/plain"), "<a></b>", &[], &[], &mut vec![]).unwrap(),
+            "<a></b>"
+        );
+        assert!(normalize_body(
+            None,
+            Some("application/xml"),
+            "<a></b>",
+            &[],
+            &[],
+            &mut vec![]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn normalize_body_should_collect_missing_paths() {
+        let skip = ["id".to_string(), "meta.at".to_string()];
+        let mut warnings = vec![];
+        let body = normalize_body(
+            None,
+            Some("application/json"),
+            r#"{"id": 1}"#,
+            &skip,
+            &[],
+            &mut warnings,
+        )
+        .unwrap();
+        assert_eq!(body, "{}");
+        assert_eq!(
+            warnings,
+            vec!["skip_body path `meta.at` not found in response body"]
+        );
+    }
+}
@@ -0,0 +1,464 @@
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::include::{load_includes, resolve_parts, Included};
+use super::inherit::inherit_profiles;
+use super::interpolate::{interpolate_env, interpolate_vars, VARS_KEY};
+use super::{ConfigFormat, ValidateConfig};
+
+/// Result of checking every profile of a config
+#[derive(Debug, Default)]
+pub struct ConfigCheck {
+    /// names of the profiles that were checked, in the order of the config
+    pub profiles: Vec<String>,
+    pub issues: Vec<ConfigIssue>,
+}
+
+/// A problem found in a config
+#[derive(Debug)]
+pub struct ConfigIssue {
+    /// the broken profile, none if the config as a whole is broken
+    pub profile: Option<String>,
+    /// the included file the profile comes from, none for the checked config itself
+    pub file: Option<PathBuf>,
+    /// 1-based line in the config, of the profile name for a broken profile
+    pub line: Option<usize>,
+    /// 1-based column in the config, of the profile name for a broken profile
+    pub column: Option<usize>,
+    pub error: anyhow::Error,
+}
+
+impl ConfigCheck {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// render the issues, prefixed by `source:line:column` or `source:line`, with a summary line
+    pub fn render(&self, source: &str) -> String {
+        let mut output = String::new();
+        for issue in &self.issues {
+            let file = match &issue.file {
+                Some(file) => file.display().to_string(),
+                None => source.to_string(),
+            };
+            let location = match (issue.line, issue.column) {
+                (Some(line), Some(column)) => format!("{}:{}:{}", file, line, column),
+                (Some(line), None) => format!("{}:{}", file, line),
+                _ => file,
+            };
+            output.push_str(&format!("{}: {:?}\n\n", location, issue.error));
+        }
+        output.push_str(&format!(
+            "{}: {} checked, {}\n",
+            source,
+            plural(self.profiles.len(), "profile"),
+            plural(self.issues.len(), "error")
+        ));
+        output
+    }
+
+    fn push(&mut self, position: Position, error: anyhow::Error) {
+        self.issues.push(ConfigIssue {
+            profile: None,
+            file: None,
+            line: position.0,
+            column: position.1,
+            error,
+        });
+    }
+}
+
+/// line and column of an error, either may be unknown
+type Position = (Option<usize>, Option<usize>);
+
+/// Every profile of a config loaded on its own, as a config holding only that profile
+pub(crate) struct EachProfile<T> {
+    /// the loaded profiles, in the order of the config
+    pub profiles: Vec<(String, Result<T>)>,
+    /// file each profile was loaded from, profiles of the config itself are not listed
+    pub files: HashMap<String, PathBuf>,
+    /// errors of the config as a whole with their position, e.g. a yaml syntax error
+    pub errors: Vec<(Position, anyhow::Error)>,
+}
+
+/// Load each profile on its own, so that a broken profile fails only itself. Errors which
+/// affect every profile, such as a syntax error or a broken `vars` section, are collected in
+/// `errors`
+pub(crate) fn load_each<T>(
+    content: &str,
+    path: Option<&Path>,
+    format: ConfigFormat,
+    vars: &[(String, String)],
+) -> EachProfile<T>
+where
+    T: DeserializeOwned,
+{
+    let mut each = EachProfile {
+        profiles: vec![],
+        files: HashMap::new(),
+        errors: vec![],
+    };
+    // parse on its own first for the position of yaml syntax errors
+    if format == ConfigFormat::Yaml {
+        if let Err(e) = serde_yaml::from_str::<Value>(content) {
+            let location = e.location();
+            let position = (
+                location.as_ref().map(|l| l.line()),
+                location.map(|l| l.column()),
+            );
+            each.errors.push((position, e.into()));
+            return each;
+        }
+    }
+    let Included { mut value, files } = match load_includes(content, path, format) {
+        Ok(included) => included,
+        Err(e) => {
+            each.errors.push(((None, None), e));
+            return each;
+        }
+    };
+    each.files = files;
+
+    // keep the order of the config, profiles failing to inherit come first otherwise
+    let order: Vec<String> = value
+        .as_mapping()
+        .map(|m| {
+            m.keys()
+                .filter_map(|k| k.as_str())
+                .map(|k| k.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    for (name, e) in inherit_profiles(&mut value) {
+        each.profiles.push((name, Err(e)));
+    }
+    resolve_parts(&mut value);
+
+    let mapping = match value {
+        Value::Mapping(mapping) => mapping,
+        _ => Mapping::new(),
+    };
+
+    // vars are shared by every profile, a broken one would fail all of them
+    let mut shared = Mapping::new();
+    if let Some(v) = mapping.get(VARS_KEY) {
+        shared.insert(VARS_KEY.into(), v.clone());
+        let mut config = Value::Mapping(shared.clone());
+        if let Err(e) =
+            interpolate_env(&mut config).and_then(|_| interpolate_vars(&mut config, vars))
+        {
+            each.errors
+                .push((key_position(content, VARS_KEY, format), e));
+            each.profiles.clear();
+            return each;
+        }
+    }
+
+    for (key, profile) in mapping {
+        let name = match key.as_str() {
+            Some(VARS_KEY) => continue,
+            Some(name) => name.to_string(),
+            None => {
+                let error = anyhow!("profile name must be a string, got {:?}", key);
+                each.errors.push(((None, None), error));
+                continue;
+            }
+        };
+
+        let mut config = shared.clone();
+        config.insert(key, profile);
+        let config = load_profile::<T>(&name, Value::Mapping(config), vars);
+        each.profiles.push((name, config));
+    }
+
+    let index = |name: &str| order.iter().position(|o| o == name);
+    each.profiles.sort_by_key(|(name, _)| index(name));
+    each
+}
+
+/// Load and validate each profile on its own, so that every broken profile is reported
+pub(crate) fn check_yaml<T>(
+    content: &str,
+    path: Option<&Path>,
+    format: ConfigFormat,
+    vars: &[(String, String)],
+) -> ConfigCheck
+where
+    T: ValidateConfig + DeserializeOwned,
+{
+    let EachProfile {
+        profiles,
+        files,
+        errors,
+    } = load_each::<T>(content, path, format, vars);
+
+    let mut check = ConfigCheck::default();
+    for (position, error) in errors {
+        check.push(position, error);
+    }
+    for (name, config) in profiles {
+        if let Err(error) = config.and_then(|config| config.validate()) {
+            // the issue of a profile, located in the file it comes from
+            let file = files.get(&name).cloned();
+            let (line, column) = match &file {
+                Some(file) => match std::fs::read_to_string(file) {
+                    Ok(content) => key_position(&content, &name, ConfigFormat::from_path(file)),
+                    Err(_) => (None, None),
+                },
+                None => key_position(content, &name, format),
+            };
+            check.issues.push(ConfigIssue {
+                profile: Some(name.clone()),
+                file,
+                line,
+                column,
+                error,
+            });
+        }
+        check.profiles.push(name);
+    }
+    check
+}
+
+fn load_profile<T>(name: &str, mut config: Value, vars: &[(String, String)]) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    interpolate_env(&mut config)?;
+    interpolate_vars(&mut config, vars)?;
+    serde_yaml::from_value(config).with_context(|| format!("failed to parse profile: {}", name))
+}
+
+fn plural(n: usize, word: &str) -> String {
+    match n {
+        1 => format!("1 {}", word),
+        n => format!("{} {}s", n, word),
+    }
+}
+
+/// position of a top-level key, found by scanning the text of the config in its format
+fn key_position(content: &str, key: &str, format: ConfigFormat) -> Position {
+    let position = match format {
+        ConfigFormat::Yaml => yaml_key_position(content, key),
+        ConfigFormat::Json => json_key_position(content, key),
+        ConfigFormat::Toml => toml_key_position(content, key),
+    };
+    match position {
+        Some((line, column)) => (Some(line), Some(column)),
+        None => (None, None),
+    }
+}
+
+/// a top-level yaml key starts a line, plain or quoted
+fn yaml_key_position(content: &str, key: &str) -> Option<(usize, usize)> {
+    let candidates = [
+        format!("{}:", key),
+        format!("\"{}\":", key),
+        format!("'{}':", key),
+    ];
+    content
+        .lines()
+        .position(|line| candidates.iter().any(|c| line.starts_with(c.as_str())))
+        .map(|i| (i + 1, 1))
+}
+
+/// a top-level json key is a string followed by `:` in the outermost object
+fn json_key_position(content: &str, key: &str) -> Option<(usize, usize)> {
+    let (mut line, mut column, mut depth) = (1, 0, 0);
+    let mut chars = content.chars();
+    while let Some(c) = chars.next() {
+        column += 1;
+        match c {
+            '\n' => (line, column) = (line + 1, 0),
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            '"' => {
+                let start = column;
+                let mut s = String::new();
+                while let Some(c) = chars.next() {
+                    column += 1;
+                    match c {
+                        '"' => break,
+                        '\\' => {
+                            s.extend(chars.next());
+                            column += 1;
+                        }
+                        c => s.push(c),
+                    }
+                }
+                let is_key = chars.clone().find(|c| !c.is_whitespace()) == Some(':');
+                if depth == 1 && is_key && s == key {
+                    return Some((line, start));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// a toml profile is a `[key]` or `[key.sub]` table, or a `key = ` or `key.sub = ` entry
+/// before the first table
+fn toml_key_position(content: &str, key: &str) -> Option<(usize, usize)> {
+    let first = |path: &str| {
+        let first = path.split('.').next().unwrap_or_default().trim();
+        first.trim_matches(|c| c == '"' || c == '\'') == key
+    };
+    let mut in_table = false;
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let column = line.len() - trimmed.len() + 1;
+        if let Some(header) = trimmed.strip_prefix('[') {
+            in_table = true;
+            let header = header.trim_start_matches('[');
+            if first(header.split(']').next().unwrap_or_default()) {
+                return Some((i + 1, column));
+            }
+        } else if !in_table && !trimmed.starts_with('#') {
+            if let Some((path, _)) = trimmed.split_once('=') {
+                if first(path) {
+                    return Some((i + 1, column));
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DiffConfig;
+
+    #[test]
+    fn check_yaml_should_report_every_broken_profile() {
+        let content = r#"
+good:
+  req1:
+    url: https://a.com/
+  req2:
+    url: https://b.com/
+bad_params:
+  req1:
+    url: https://a.com/
+    params: abcd
+  req2:
+    url: https://b.com/
+bad_url:
+  req1:
+    url: not a url
+  req2:
+    url: https://b.com/{{id}}
+"#;
+        let check = check_yaml::<DiffConfig>(content, None, ConfigFormat::Yaml, &[]);
+        assert_eq!(check.profiles, vec!["good", "bad_params", "bad_url"]);
+        assert_eq!(check.issues.len(), 2);
+
+        let issue = &check.issues[0];
+        assert_eq!(issue.profile.as_deref(), Some("bad_params"));
+        assert_eq!(issue.line, Some(7));
+        assert_eq!(
+            issue.error.to_string(),
+            "failed to validate profile: bad_params"
+        );
+
+        let issue = &check.issues[1];
+        assert_eq!(issue.profile.as_deref(), Some("bad_url"));
+        assert_eq!(issue.line, Some(13));
+        assert!(format!("{:?}", issue.error).contains("unresolved placeholder `{{id}}`"));
+    }
+
+    #[test]
+    fn check_yaml_should_report_broken_extends_in_order() {
+        let content = "a:\n  extends: b\nb:\n  extends: a\nc:\n  extends: d\n";
+        let check = check_yaml::<DiffConfig>(content, None, ConfigFormat::Yaml, &[]);
+        assert_eq!(check.profiles, vec!["a", "b", "c"]);
+        let lines: Vec<_> = check.issues.iter().map(|i| i.line).collect();
+        assert_eq!(lines, vec![Some(1), Some(3), Some(5)]);
+        assert!(check.issues.iter().all(|i| i.column == Some(1)));
+    }
+
+    #[test]
+    fn load_each_should_load_good_profiles_next_to_broken_ones() {
+        let content = r#"
+vars:
+  host: a.com
+bad:
+  extends: bad
+good:
+  req1:
+    url: https://{{host}}/
+  req2:
+    url: https://b.com/{{id}}
+"#;
+        let each = load_each::<DiffConfig>(content, None, ConfigFormat::Yaml, &[]);
+        assert!(each.errors.is_empty());
+        let names: Vec<_> = each
+            .profiles
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, vec!["bad", "good"]);
+        assert!(each.profiles[0].1.is_err());
+        assert!(each.profiles[1].1.is_err());
+
+        let vars = [("id".to_string(), "1".to_string())];
+        let each = load_each::<DiffConfig>(content, None, ConfigFormat::Yaml, &vars);
+        let config = each.profiles[1].1.as_ref().unwrap();
+        assert_eq!(config.profiles["good"].req1.url.as_str(), "https://a.com/");
+    }
+
+    #[test]
+    fn check_yaml_should_report_syntax_error_position() {
+        let check = check_yaml::<DiffConfig>("a:\n  b: [1\n", None, ConfigFormat::Yaml, &[]);
+        assert!(check.profiles.is_empty());
+        assert_eq!(check.issues.len(), 1);
+        assert!(check.issues[0].line.is_some());
+        assert!(check.issues[0].column.is_some());
+        assert!(!check.is_ok());
+        assert!(check
+            .render("test.yml")
+            .ends_with("\n\ntest.yml: 0 profiles checked, 1 error\n"));
+    }
+
+    #[test]
+    fn check_yaml_should_locate_profiles_of_json_and_toml() {
+        let json = r#"{
+  "vars": {"bad": 1},
+  "good": {
+    "req1": {"url": "https://a.com/", "params": "bad"},
+    "req2": {"url": "https://b.com/"}
+  },
+  "bad": {
+    "req1": {"url": "https://a.com/", "params": "bad"},
+    "req2": {"url": "https://b.com/"}
+  }
+}"#;
+        let check = check_yaml::<DiffConfig>(json, None, ConfigFormat::Json, &[]);
+        let positions: Vec<_> = check.issues.iter().map(|i| (i.line, i.column)).collect();
+        assert_eq!(positions, vec![(Some(3), Some(3)), (Some(7), Some(3))]);
+
+        let toml = r#"
+good.req1 = { url = "https://a.com/", params = "bad" }
+good.req2 = { url = "https://b.com/" }
+
+[vars]
+bad = 1
+
+[bad.req1]
+url = "https://a.com/"
+params = "bad"
+
+[bad.req2]
+url = "https://b.com/"
+"#;
+        let check = check_yaml::<DiffConfig>(toml, None, ConfigFormat::Toml, &[]);
+        // toml tables are sorted by name
+        let mut positions: Vec<_> = check.issues.iter().map(|i| (i.line, i.column)).collect();
+        positions.sort();
+        assert_eq!(positions, vec![(Some(2), Some(1)), (Some(8), Some(1))]);
+    }
+}
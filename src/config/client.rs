@@ -0,0 +1,222 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::{tls, Certificate, Client, Identity, NoProxy, Proxy};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::{is_default, RequestProfile, ValidateConfig};
+
+/// TLS options of a request, paths are relative to the current directory
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct TlsConfig {
+    /// pem bundle of CAs trusted in addition to the built-in roots
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub ca_cert: Option<PathBuf>,
+    /// pem client certificate for mTLS, it may also hold the private key
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub client_cert: Option<PathBuf>,
+    /// pem private key of the client certificate
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub client_key: Option<PathBuf>,
+    /// accept invalid certificates and host names, only for servers you trust
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub insecure: bool,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub min_version: Option<TlsVersion>,
+}
+
+/// Minimum TLS version, `1.0` to `1.3`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsVersion {
+    Tls1_0,
+    Tls1_1,
+    Tls1_2,
+    Tls1_3,
+}
+
+impl RequestProfile {
+    /// the http client with the timeouts, TLS and proxy options of the profile. Without a
+    /// `proxy`, the `HTTP_PROXY` / `HTTPS_PROXY` / `NO_PROXY` env vars are used
+    pub(crate) fn client(&self) -> Result<Client> {
+        let mut client = Client::builder();
+        if let Some(timeout) = self.timeout {
+            client = client.timeout(timeout);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            client = client.connect_timeout(connect_timeout);
+        }
+
+        if let Some(tls) = &self.tls {
+            if let Some(path) = &tls.ca_cert {
+                // every certificate of the bundle is added
+                client = client.add_root_certificate(Certificate::from_pem(&read(path)?)?);
+            }
+            if let Some(path) = &tls.client_cert {
+                let mut pem = read(path)?;
+                if let Some(key) = &tls.client_key {
+                    pem.push(b'\n');
+                    pem.extend(read(key)?);
+                }
+                let identity = Identity::from_pem(&pem)
+                    .with_context(|| format!("invalid client certificate {}", path.display()))?;
+                client = client.identity(identity);
+            }
+            if tls.insecure {
+                client = client.danger_accept_invalid_certs(true);
+            }
+            if let Some(version) = tls.min_version {
+                client = client.min_tls_version(version.into());
+            }
+        }
+
+        if let Some(url) = &self.proxy {
+            // NO_PROXY is honoured for the proxy of the profile as well
+            let mut no_proxy = self.no_proxy.clone();
+            no_proxy.extend(
+                ["NO_PROXY", "no_proxy"]
+                    .iter()
+                    .find_map(|key| std::env::var(key).ok()),
+            );
+            let proxy =
+                Proxy::all(url.clone())?.no_proxy(NoProxy::from_string(&no_proxy.join(",")));
+            client = client.proxy(proxy);
+        }
+
+        Ok(client.build()?)
+    }
+}
+
+impl ValidateConfig for TlsConfig {
+    fn validate(&self) -> Result<()> {
+        if self.client_key.is_some() && self.client_cert.is_none() {
+            return Err(anyhow!("tls client_key is given without a client_cert"));
+        }
+        Ok(())
+    }
+}
+
+/// the proxy url must be one reqwest can connect through
+pub(crate) fn validate_proxy(url: &url::Url) -> Result<()> {
+    match url.scheme() {
+        "http" | "https" | "socks5" | "socks5h" => Ok(()),
+        scheme => Err(anyhow!(
+            "unsupported proxy scheme `{}`, expect http, https, socks5 or socks5h",
+            scheme
+        )),
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))
+}
+
+impl From<TlsVersion> for tls::Version {
+    fn from(version: TlsVersion) -> Self {
+        match version {
+            TlsVersion::Tls1_0 => tls::Version::TLS_1_0,
+            TlsVersion::Tls1_1 => tls::Version::TLS_1_1,
+            TlsVersion::Tls1_2 => tls::Version::TLS_1_2,
+            TlsVersion::Tls1_3 => tls::Version::TLS_1_3,
+        }
+    }
+}
+
+impl fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = match self {
+            Self::Tls1_0 => "1.0",
+            Self::Tls1_1 => "1.1",
+            Self::Tls1_2 => "1.2",
+            Self::Tls1_3 => "1.3",
+        };
+        write!(f, "{}", version)
+    }
+}
+
+impl Serialize for TlsVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+// `min_version: 1.2` is a number in yaml, so both numbers and strings are accepted
+impl<'de> Deserialize<'de> for TlsVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = TlsVersion;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a TLS version, 1.0 to 1.3")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<TlsVersion, E> {
+                match v.trim_start_matches("TLS").trim_start_matches('v') {
+                    "1.0" | "1" => Ok(TlsVersion::Tls1_0),
+                    "1.1" => Ok(TlsVersion::Tls1_1),
+                    "1.2" => Ok(TlsVersion::Tls1_2),
+                    "1.3" => Ok(TlsVersion::Tls1_3),
+                    _ => Err(E::invalid_value(de::Unexpected::Str(v), &self)),
+                }
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<TlsVersion, E> {
+                self.visit_str(&format!("{:.1}", v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<TlsVersion, E> {
+                self.visit_str(&format!("{}.0", v))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn tls_version_should_parse_numbers_and_strings() {
+        let tls: TlsConfig = serde_yaml::from_str("min_version: 1.2").unwrap();
+        assert_eq!(tls.min_version, Some(TlsVersion::Tls1_2));
+        let tls: TlsConfig = serde_yaml::from_str("min_version: TLSv1.3").unwrap();
+        assert_eq!(tls.min_version, Some(TlsVersion::Tls1_3));
+        assert!(serde_yaml::from_str::<TlsConfig>("min_version: 1.4").is_err());
+        assert_eq!(serde_yaml::to_string(&tls).unwrap(), "min_version: '1.3'\n");
+    }
+
+    #[test]
+    fn request_profile_client_should_load_tls_and_proxy() {
+        let mut profile = RequestProfile::from_str("https://localhost/todo").unwrap();
+        profile.tls = Some(TlsConfig {
+            ca_cert: Some(fixture("tls/ca.pem")),
+            client_cert: Some(fixture("tls/client.pem")),
+            client_key: Some(fixture("tls/client.key")),
+            insecure: true,
+            min_version: Some(TlsVersion::Tls1_2),
+        });
+        profile.proxy = Some("socks5h://localhost:1080".parse().unwrap());
+        profile.no_proxy = vec!["localhost".into()];
+        assert!(profile.client().is_ok());
+
+        profile.tls = Some(TlsConfig {
+            ca_cert: Some(fixture("tls/missing.pem")),
+            ..Default::default()
+        });
+        let err = profile.client().unwrap_err();
+        assert!(err.to_string().starts_with("failed to read "));
+
+        let proxy = "ftp://localhost:21".parse().unwrap();
+        assert!(validate_proxy(&proxy).is_err());
+    }
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name)
+    }
+}
@@ -0,0 +1,90 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_yaml::Value;
+use std::path::Path;
+
+/// File format of a config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ConfigFormat {
+    #[default]
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// detect the format from the file extension, yaml if it is not `.json` or `.toml`
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::Json,
+            Some("toml") => Self::Toml,
+            _ => Self::Yaml,
+        }
+    }
+
+    /// file extension of the format, also used to pick the syntax for highlighting
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Yaml => "yaml",
+            Self::Json => "json",
+            Self::Toml => "toml",
+        }
+    }
+
+    /// parse the config into a yaml value, which is what the config is preprocessed on
+    pub fn parse(&self, content: &str) -> Result<Value> {
+        Ok(match self {
+            Self::Yaml => serde_yaml::from_str(content)?,
+            Self::Json => serde_json::from_str(content)?,
+            Self::Toml => toml::from_str(content)?,
+        })
+    }
+
+    /// serialize the config in this format
+    pub fn to_string<T: Serialize>(&self, config: &T) -> Result<String> {
+        Ok(match self {
+            Self::Yaml => format!("---\n{}", serde_yaml::to_string(config)?),
+            Self::Json => format!("{}\n", serde_json::to_string_pretty(config)?),
+            Self::Toml => toml::to_string_pretty(config)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DiffConfig, LoadConfig};
+
+    #[test]
+    fn config_format_should_round_trip() {
+        let config = DiffConfig::from_yaml(include_str!("../../fixtures/test.yml")).unwrap();
+        for format in [ConfigFormat::Yaml, ConfigFormat::Json, ConfigFormat::Toml] {
+            let content = format.to_string(&config).unwrap();
+            let loaded: DiffConfig = DiffConfig::parse_config(&content, None, format, &[]).unwrap();
+            assert_eq!(
+                serde_yaml::to_value(&loaded).unwrap(),
+                serde_yaml::to_value(&config).unwrap(),
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn config_format_from_path_should_work() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("a.json")),
+            ConfigFormat::Json
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("a/b.toml")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("a.yml")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(ConfigFormat::from_path(Path::new("-")), ConfigFormat::Yaml);
+    }
+}
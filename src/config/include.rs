@@ -0,0 +1,430 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde_yaml::value::{Tag, TaggedValue};
+use serde_yaml::{Mapping, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use super::interpolate::VARS_KEY;
+use super::ConfigFormat;
+use crate::print_warning;
+
+/// top-level key listing the files, or glob patterns, to merge into the config
+const INCLUDE_KEY: &str = "include";
+
+/// tag of a `@path` file part, followed by the directory of the file declaring it
+const PART_DIR_TAG: &str = "xdiff-part-dir:";
+
+/// A config merged with all the files it includes
+#[derive(Debug, Default)]
+pub(crate) struct Included {
+    pub value: Value,
+    /// file each profile was loaded from, profiles of the including config are not listed
+    pub files: HashMap<String, PathBuf>,
+}
+
+/// Parse the config and merge the profiles of every file in its `include` list, recursively.
+/// Relative paths are resolved against the including file, or the current directory if the
+/// config was not read from a file. `vars` of the including file take precedence. Relative
+/// paths in the profiles, see `resolve_paths`, are resolved against the file declaring them;
+/// multipart file parts only once `resolve_parts` knows the content type of the merged profile.
+pub(crate) fn load_includes(
+    content: &str,
+    path: Option<&Path>,
+    format: ConfigFormat,
+) -> Result<Included> {
+    let value = format.parse(content)?;
+
+    let mut loader = Loader::default();
+    let dir = match path {
+        Some(path) => {
+            loader.loaded.insert(canonical(path));
+            path.parent().map(Path::to_path_buf).unwrap_or_default()
+        }
+        None => PathBuf::new(),
+    };
+    let label = path.map_or_else(|| "<config>".to_string(), |p| p.display().to_string());
+    loader.merge(value, &label, None, &dir)?;
+
+    Ok(Included {
+        value: Value::Mapping(loader.merged),
+        files: loader.files,
+    })
+}
+
+#[derive(Debug, Default)]
+struct Loader {
+    merged: Mapping,
+    files: HashMap<String, PathBuf>,
+    /// the file each profile was loaded from, for duplicate errors
+    labels: HashMap<String, String>,
+    /// files already merged, a file included twice is merged only once
+    loaded: HashSet<PathBuf>,
+}
+
+impl Loader {
+    fn merge(&mut self, value: Value, label: &str, file: Option<&Path>, dir: &Path) -> Result<()> {
+        let mapping = match value {
+            Value::Mapping(mapping) => mapping,
+            Value::Null => Mapping::new(),
+            _ => bail!("{} must be a mapping of profile name to profile", label),
+        };
+
+        let mut includes = None;
+        for (key, mut value) in mapping {
+            let name = match key.as_str() {
+                Some(INCLUDE_KEY) => {
+                    includes = Some(value);
+                    continue;
+                }
+                Some(VARS_KEY) => {
+                    self.merge_vars(value);
+                    continue;
+                }
+                Some(name) => name.to_string(),
+                // not a valid profile name, left to deserialization to report
+                None => {
+                    self.merged.insert(key, value);
+                    continue;
+                }
+            };
+            if let Some(first) = self.labels.get(&name) {
+                bail!("duplicate profile `{}` in {} and {}", name, first, label);
+            }
+            self.labels.insert(name.clone(), label.to_string());
+            if let Some(file) = file {
+                self.files.insert(name, file.to_path_buf());
+            }
+            resolve_paths(&mut value, dir);
+            self.merged.insert(key, value);
+        }
+
+        for pattern in include_patterns(includes, label)? {
+            for path in expand(dir, &pattern)? {
+                if !self.loaded.insert(canonical(&path)) {
+                    continue;
+                }
+                let content = std::fs::read_to_string(&path).with_context(|| {
+                    format!("failed to read {} included by {}", path.display(), label)
+                })?;
+                let value = ConfigFormat::from_path(&path)
+                    .parse(&content)
+                    .with_context(|| format!("failed to parse {}", path.display()))?;
+                let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+                self.merge(value, &path.display().to_string(), Some(&path), &dir)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// vars which are already set win, so the including file overrides the included ones
+    fn merge_vars(&mut self, vars: Value) {
+        match (self.merged.get_mut(VARS_KEY), vars) {
+            (Some(Value::Mapping(merged)), Value::Mapping(vars)) => {
+                for (k, v) in vars {
+                    if !merged.contains_key(&k) {
+                        merged.insert(k, v);
+                    }
+                }
+            }
+            (Some(_), _) => {}
+            (None, vars) => {
+                self.merged.insert(VARS_KEY.into(), vars);
+            }
+        }
+    }
+}
+
+/// resolve the relative paths of the requests of a profile against `dir`: the tls certs and
+/// key and `body_file`. `@path` parts of a body are tagged with `dir`, whether the body is
+/// multipart is only known after `extends` is merged, see `resolve_parts`. An xreq profile is
+/// a request, an xdiff profile holds `req1` and `req2`. Paths starting with a `${VAR}` or
+/// `{{name}}` placeholder are left as is, they are filled in later
+fn resolve_paths(profile: &mut Value, dir: &Path) {
+    if dir.as_os_str().is_empty() {
+        return;
+    }
+    for_each_request(profile, |req| resolve_request_paths(req, dir));
+}
+
+fn for_each_request(profile: &mut Value, mut f: impl FnMut(&mut Mapping)) {
+    if let Some(req) = profile.as_mapping_mut() {
+        f(req);
+    }
+    for key in ["req1", "req2"] {
+        if let Some(req) = profile.get_mut(key).and_then(Value::as_mapping_mut) {
+            f(req);
+        }
+    }
+}
+
+fn resolve_request_paths(req: &mut Mapping, dir: &Path) {
+    if let Some(tls) = req.get_mut("tls").and_then(Value::as_mapping_mut) {
+        for key in ["ca_cert", "client_cert", "client_key"] {
+            if let Some(Value::String(path)) = tls.get_mut(key) {
+                *path = resolve_path(path, dir);
+            }
+        }
+    }
+    if let Some(Value::String(path)) = req.get_mut("body_file") {
+        *path = resolve_path(path, dir);
+    }
+    for_each_part(req, |part| tag_part(part, dir));
+}
+
+/// Resolve the `@path` file parts of multipart bodies against the file declaring them, once
+/// profiles are merged with the profiles they extend. Parts of other bodies are kept as is.
+pub(crate) fn resolve_parts(config: &mut Value) {
+    let profiles = match config.as_mapping_mut() {
+        Some(profiles) => profiles,
+        None => return,
+    };
+    for (key, profile) in profiles.iter_mut() {
+        if key.as_str() == Some(VARS_KEY) {
+            continue;
+        }
+        for_each_request(profile, |req| {
+            let multipart = is_multipart(req);
+            for_each_part(req, |part| untag_part(part, multipart));
+        });
+    }
+}
+
+fn is_multipart(req: &Mapping) -> bool {
+    let content_type = req
+        .get("headers")
+        .and_then(Value::as_mapping)
+        .and_then(|headers| {
+            headers.iter().find_map(|(k, v)| match k.as_str() {
+                Some(k) if k.eq_ignore_ascii_case("content-type") => v.as_str(),
+                _ => None,
+            })
+        });
+    content_type.is_some_and(|v| v.split(';').next().map(str::trim) == Some("multipart/form-data"))
+}
+
+fn for_each_part(req: &mut Mapping, mut f: impl FnMut(&mut Value)) {
+    if let Some(Value::Mapping(body)) = req.get_mut("body") {
+        for value in body.values_mut() {
+            match value {
+                Value::Sequence(values) => values.iter_mut().for_each(&mut f),
+                value => f(value),
+            }
+        }
+    }
+}
+
+/// tag a `@path;filename=name` file part with the directory of its file, `@@` is an escaped
+/// text part
+fn tag_part(value: &mut Value, dir: &Path) {
+    if let Value::String(s) = value {
+        if s.starts_with('@') && !s.starts_with("@@") {
+            let tag = Tag::new(format!("{}{}", PART_DIR_TAG, dir.display()));
+            let part = std::mem::take(value);
+            *value = Value::Tagged(Box::new(TaggedValue { tag, value: part }));
+        }
+    }
+}
+
+/// drop the tag of a file part, resolving its path against the tagged directory if the body
+/// is multipart
+fn untag_part(value: &mut Value, multipart: bool) {
+    let dir = match value {
+        Value::Tagged(tagged) => match tagged.tag.to_string().strip_prefix('!') {
+            Some(tag) => match tag.strip_prefix(PART_DIR_TAG) {
+                Some(dir) => PathBuf::from(dir),
+                None => return,
+            },
+            None => return,
+        },
+        _ => return,
+    };
+    let part = match std::mem::take(value) {
+        Value::Tagged(tagged) => tagged.value,
+        _ => unreachable!("checked above"),
+    };
+    *value = match part {
+        Value::String(s) if multipart => Value::String(format!("@{}", resolve_path(&s[1..], &dir))),
+        part => part,
+    };
+}
+
+fn resolve_path(path: &str, dir: &Path) -> String {
+    if path.starts_with("${") || path.starts_with("{{") || Path::new(path).is_absolute() {
+        return path.to_string();
+    }
+    dir.join(path).display().to_string()
+}
+
+fn include_patterns(includes: Option<Value>, label: &str) -> Result<Vec<String>> {
+    let invalid = || {
+        anyhow!(
+            "`{}` in {} must be a path or a list of paths",
+            INCLUDE_KEY,
+            label
+        )
+    };
+    match includes {
+        None => Ok(vec![]),
+        Some(Value::String(pattern)) => Ok(vec![pattern]),
+        Some(Value::Sequence(patterns)) => patterns
+            .into_iter()
+            .map(|v| v.as_str().map(|s| s.to_string()).ok_or_else(invalid))
+            .collect(),
+        Some(_) => Err(invalid()),
+    }
+}
+
+/// files matching the pattern, sorted; a path without glob characters is returned as is
+fn expand(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    let path = dir.join(pattern);
+    if !pattern.contains(['*', '?', '[']) {
+        return Ok(vec![path]);
+    }
+
+    let full = path
+        .to_str()
+        .ok_or_else(|| anyhow!("invalid include pattern: {}", path.display()))?;
+    let mut paths = glob::glob(full)
+        .with_context(|| format!("invalid include pattern: {}", pattern))?
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.sort();
+    if paths.is_empty() {
+        print_warning(&format!("include pattern `{}` matched no files", full));
+    }
+    Ok(paths)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::inherit::resolve_extends;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn load_includes_should_merge_files() {
+        let path = fixture("include.yml");
+        let content = std::fs::read_to_string(&path).unwrap();
+        let included = load_includes(&content, Some(&path), ConfigFormat::Yaml).unwrap();
+
+        let names: Vec<_> = included
+            .value
+            .as_mapping()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["vars", "todo", "todo_a", "todo_b"]);
+        assert_eq!(included.value["vars"]["id"], Value::from(1));
+        assert_eq!(included.value["vars"]["team"], Value::from("a"));
+        assert_eq!(included.files["todo_a"], fixture("include/a.yml"));
+        assert!(!included.files.contains_key("todo"));
+    }
+
+    #[test]
+    fn load_includes_should_reject_duplicate_profiles() {
+        let path = fixture("include.yml");
+        let content = "include: include/*.yml\ntodo_b:\n  req1: {}\n";
+        let err = load_includes(content, Some(&path), ConfigFormat::Yaml).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "duplicate profile `todo_b` in {} and {}",
+                path.display(),
+                fixture("include/b.yml").display()
+            )
+        );
+    }
+
+    #[test]
+    fn load_includes_should_resolve_paths_against_the_declaring_file() {
+        let content = r#"
+todo:
+  req1:
+    url: https://a.com/
+    tls:
+      ca_cert: certs/ca.pem
+      client_cert: /etc/client.pem
+      client_key: ${KEY_FILE}
+  req2:
+    url: https://b.com/
+    headers:
+      content-type: multipart/form-data
+    body:
+      file: "@data/a.txt;type=text/plain"
+      files: ["@b.txt", "c", "@@d"]
+upload:
+  url: https://a.com/
+  body_file: data/body.bin
+  body:
+    file: "@data/a.txt"
+"#;
+        let path = Path::new("conf").join("xdiff.yml");
+        let mut value = load_includes(content, Some(&path), ConfigFormat::Yaml)
+            .unwrap()
+            .value;
+        resolve_parts(&mut value);
+
+        let dir = Path::new("conf");
+        let resolved = |path: &str| Value::from(dir.join(path).display().to_string());
+        let tls = &value["todo"]["req1"]["tls"];
+        assert_eq!(tls["ca_cert"], resolved("certs/ca.pem"));
+        assert_eq!(tls["client_cert"], Value::from("/etc/client.pem"));
+        assert_eq!(tls["client_key"], Value::from("${KEY_FILE}"));
+        let body = &value["todo"]["req2"]["body"];
+        let part = format!("@{};type=text/plain", dir.join("data/a.txt").display());
+        assert_eq!(body["file"], Value::from(part));
+        let part = format!("@{}", dir.join("b.txt").display());
+        assert_eq!(body["files"][0], Value::from(part));
+        assert_eq!(body["files"][1], Value::from("c"));
+        assert_eq!(body["files"][2], Value::from("@@d"));
+
+        assert_eq!(value["upload"]["body_file"], resolved("data/body.bin"));
+        // not a multipart body
+        assert_eq!(value["upload"]["body"]["file"], Value::from("@data/a.txt"));
+    }
+
+    #[test]
+    fn resolve_parts_should_resolve_inherited_multipart_bodies() {
+        let dir = std::env::temp_dir().join(format!("xdiff-include-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("parts")).unwrap();
+        let upload = r#"
+_defaults:
+  headers:
+    content-type: multipart/form-data
+multipart:
+  url: https://a.com/
+  body:
+    other: "@b.txt"
+"#;
+        std::fs::write(dir.join("parts/upload.yml"), upload).unwrap();
+        let content = r#"
+include: parts/upload.yml
+upload:
+  extends: multipart
+  body:
+    file: "@data/a.txt"
+"#;
+        let path = dir.join("xdiff.yml");
+        let mut value = load_includes(content, Some(&path), ConfigFormat::Yaml)
+            .unwrap()
+            .value;
+        resolve_extends(&mut value).unwrap();
+        resolve_parts(&mut value);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let body = &value["upload"]["body"];
+        let part = format!("@{}", dir.join("data/a.txt").display());
+        assert_eq!(body["file"], Value::from(part));
+        let part = format!("@{}", dir.join("parts/b.txt").display());
+        assert_eq!(body["other"], Value::from(part));
+    }
+}
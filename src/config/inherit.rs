@@ -0,0 +1,227 @@
+use anyhow::{anyhow, Result};
+use serde_yaml::{Mapping, Value};
+
+use super::interpolate::VARS_KEY;
+
+/// profile every other profile inherits from, it is not a profile on its own
+const DEFAULTS_KEY: &str = "_defaults";
+const EXTENDS_KEY: &str = "extends";
+
+/// Merge every profile with the profile it `extends`, or with `_defaults` if it has no
+/// `extends`. The parent is merged first, so the profile overrides it.
+pub(crate) fn resolve_extends(config: &mut Value) -> Result<()> {
+    match inherit_profiles(config).into_iter().next() {
+        Some((_, e)) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Same as `resolve_extends`, but collects the error of every profile which can not be
+/// resolved. Those profiles are removed from the config.
+pub(crate) fn inherit_profiles(config: &mut Value) -> Vec<(String, anyhow::Error)> {
+    let mapping = match config.as_mapping_mut() {
+        Some(mapping) => mapping,
+        None => return vec![],
+    };
+    let defaults = mapping.remove(DEFAULTS_KEY);
+    let names: Vec<String> = mapping
+        .keys()
+        .filter_map(|k| k.as_str())
+        .filter(|k| *k != VARS_KEY)
+        .map(|k| k.to_string())
+        .collect();
+
+    let mut resolved = vec![];
+    let mut errors = vec![];
+    for name in names {
+        match resolve_profile(&name, mapping, defaults.as_ref(), &mut vec![]) {
+            Ok(profile) => resolved.push((name, profile)),
+            Err(e) => {
+                let e = e.context(format!("failed to validate profile: {}", name));
+                errors.push((name, e));
+            }
+        }
+    }
+
+    for (name, profile) in resolved {
+        mapping.insert(name.into(), profile);
+    }
+    for (name, _) in &errors {
+        mapping.remove(name.as_str());
+    }
+    errors
+}
+
+/// the profile merged with all of its parents, `chain` holds the profiles being resolved
+fn resolve_profile(
+    name: &str,
+    profiles: &Mapping,
+    defaults: Option<&Value>,
+    chain: &mut Vec<String>,
+) -> Result<Value> {
+    if chain.iter().any(|n| n == name) {
+        chain.push(name.to_string());
+        return Err(anyhow!("extends cycle: {}", chain.join(" -> ")));
+    }
+
+    let mut profile = match name {
+        DEFAULTS_KEY => defaults.cloned(),
+        _ => profiles.get(name).cloned(),
+    }
+    .ok_or_else(|| anyhow!("extends unknown profile `{}`", name))?;
+
+    let parent = match profile.as_mapping_mut().and_then(|m| m.remove(EXTENDS_KEY)) {
+        Some(Value::String(parent)) => Some(parent),
+        Some(v) => {
+            return Err(anyhow!(
+                "`{}` must be a profile name, got {:?}",
+                EXTENDS_KEY,
+                v
+            ))
+        }
+        None => None,
+    };
+    if name == DEFAULTS_KEY {
+        if parent.is_some() {
+            return Err(anyhow!("`{}` can not extend another profile", DEFAULTS_KEY));
+        }
+        return Ok(profile);
+    }
+
+    let base = match parent {
+        Some(parent) => {
+            chain.push(name.to_string());
+            let base = resolve_profile(&parent, profiles, defaults, chain)?;
+            chain.pop();
+            Some(base)
+        }
+        None => defaults.cloned(),
+    };
+
+    Ok(match base {
+        Some(base) => merge(base, profile),
+        None => profile,
+    })
+}
+
+/// deep merge mappings, anything else in `overlay` replaces the value in `base`
+fn merge(base: Value, overlay: Value) -> Value {
+    match (base, overlay) {
+        (Value::Mapping(mut base), Value::Mapping(overlay)) => {
+            for (k, v) in overlay {
+                match base.get_mut(&k) {
+                    Some(b) => *b = merge(std::mem::take(b), v),
+                    None => {
+                        base.insert(k, v);
+                    }
+                }
+            }
+            Value::Mapping(base)
+        }
+        (_, overlay) => overlay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn resolve_extends_should_deep_merge_parents() {
+        let mut config = yaml(
+            r#"
+_defaults:
+  req1:
+    headers:
+      user-agent: xdiff
+  res:
+    skip_headers: [date]
+base:
+  req1:
+    url: https://a.com/
+    params: { a: 1 }
+  req2:
+    url: https://b.com/
+child:
+  extends: base
+  req1:
+    params: { b: 2 }
+  res:
+    skip_headers: [age]
+"#,
+        );
+        resolve_extends(&mut config).unwrap();
+
+        assert_eq!(
+            config,
+            yaml(
+                r#"
+base:
+  req1:
+    headers:
+      user-agent: xdiff
+    url: https://a.com/
+    params: { a: 1 }
+  res:
+    skip_headers: [date]
+  req2:
+    url: https://b.com/
+child:
+  req1:
+    headers:
+      user-agent: xdiff
+    url: https://a.com/
+    params: { a: 1, b: 2 }
+  res:
+    skip_headers: [age]
+  req2:
+    url: https://b.com/
+"#
+            )
+        );
+    }
+
+    #[test]
+    fn resolve_extends_should_detect_cycles() {
+        let mut config = yaml(
+            r#"
+a: { extends: b }
+b: { extends: c }
+c: { extends: a }
+d: { extends: e }
+ok: { url: https://a.com/ }
+"#,
+        );
+        let errors = inherit_profiles(&mut config);
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|(name, e)| (name.as_str(), format!("{:#}", e)))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    "a",
+                    "failed to validate profile: a: extends cycle: a -> b -> c -> a".into()
+                ),
+                (
+                    "b",
+                    "failed to validate profile: b: extends cycle: b -> c -> a -> b".into()
+                ),
+                (
+                    "c",
+                    "failed to validate profile: c: extends cycle: c -> a -> b -> c".into()
+                ),
+                (
+                    "d",
+                    "failed to validate profile: d: extends unknown profile `e`".into()
+                ),
+            ]
+        );
+        assert_eq!(config, yaml("ok: { url: https://a.com/ }"));
+    }
+}
@@ -0,0 +1,280 @@
+use anyhow::{anyhow, Context, Result};
+use dialoguer::{theme::ColorfulTheme, Input};
+use serde_yaml::Value;
+use std::collections::{hash_map::Entry, HashMap};
+
+/// top-level key holding the `{{name}}` template variables, it is not a profile
+pub(crate) const VARS_KEY: &str = "vars";
+
+/// Replace `${VAR}` and `${VAR:-default}` in every string value of the config with
+/// the value of the environment variable. `$${VAR}` is kept as a literal `${VAR}`.
+pub(crate) fn interpolate_env(config: &mut Value) -> Result<()> {
+    interpolate_profiles(config, &interpolate_str)
+}
+
+/// Take the `vars` section out of the config, override it with `extra` and replace
+/// every `{{name}}` placeholder in the profiles. Unresolved placeholders are an error.
+pub(crate) fn interpolate_vars(config: &mut Value, extra: &[(String, String)]) -> Result<()> {
+    let mut vars = take_vars(config)?;
+    vars.extend(extra.iter().cloned());
+    interpolate_profiles(config, &|s| render_template(s, &vars))
+}
+
+fn take_vars(config: &mut Value) -> Result<HashMap<String, String>> {
+    let vars = match config.as_mapping_mut().and_then(|m| m.remove(VARS_KEY)) {
+        Some(Value::Mapping(vars)) => vars,
+        Some(Value::Null) | None => return Ok(HashMap::new()),
+        Some(_) => return Err(anyhow!("`{}` must be a mapping of name to value", VARS_KEY)),
+    };
+
+    let mut output = HashMap::new();
+    for (k, v) in vars {
+        let name = k
+            .as_str()
+            .ok_or_else(|| anyhow!("`{}` must be a mapping of name to value", VARS_KEY))?
+            .to_string();
+        let value = match v {
+            Value::String(s) => s,
+            Value::Number(n) => n.to_string(),
+            Value::Bool(b) => b.to_string(),
+            _ => return Err(anyhow!("var `{}` must be a string, number or bool", name)),
+        };
+        output.insert(name, value);
+    }
+    Ok(output)
+}
+
+fn interpolate_profiles(config: &mut Value, f: &dyn Fn(&str) -> Result<String>) -> Result<()> {
+    if let Value::Mapping(profiles) = config {
+        for (name, profile) in profiles.iter_mut() {
+            let name = name.as_str().unwrap_or_default();
+            let msg = match name {
+                VARS_KEY => format!("failed to validate {}", VARS_KEY),
+                _ => format!("failed to validate profile: {}", name),
+            };
+            interpolate_value(profile, &mut String::new(), f).context(msg)?;
+        }
+    }
+    Ok(())
+}
+
+fn interpolate_value(
+    value: &mut Value,
+    path: &mut String,
+    f: &dyn Fn(&str) -> Result<String>,
+) -> Result<()> {
+    match value {
+        Value::String(s) => {
+            *s = f(s).with_context(|| path.clone())?;
+        }
+        Value::Sequence(seq) => {
+            for (i, v) in seq.iter_mut().enumerate() {
+                let len = path.len();
+                path.push_str(&format!("[{}]", i));
+                interpolate_value(v, path, f)?;
+                path.truncate(len);
+            }
+        }
+        Value::Mapping(map) => {
+            for (k, v) in map.iter_mut() {
+                let len = path.len();
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(k.as_str().unwrap_or_default());
+                interpolate_value(v, path, f)?;
+                path.truncate(len);
+            }
+        }
+        Value::Tagged(tagged) => interpolate_value(&mut tagged.value, path, f)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Replace `{{name}}` placeholders in `s` with the given vars, `\{{` is kept as a literal `{{`.
+pub fn render_template(s: &str, vars: &HashMap<String, String>) -> Result<String> {
+    let mut output = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(pos) = rest.find("{{") {
+        if rest[..pos].ends_with('\\') {
+            output.push_str(&rest[..pos - 1]);
+            output.push_str("{{");
+            rest = &rest[pos + 2..];
+            continue;
+        }
+        output.push_str(&rest[..pos]);
+        let r = &rest[pos + 2..];
+        let end = r
+            .find("}}")
+            .ok_or_else(|| anyhow!("unterminated placeholder in `{}`", s))?;
+        let name = r[..end].trim();
+        let value = vars
+            .get(name)
+            .ok_or_else(|| anyhow!("unresolved placeholder `{{{{{}}}}}`", name))?;
+        output.push_str(value);
+        rest = &r[end + 2..];
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+/// Names of every `{{name}}` placeholder in `s`, in order of appearance.
+pub fn template_placeholders(s: &str) -> Vec<String> {
+    let mut output: Vec<String> = vec![];
+    let mut rest = s;
+
+    while let Some(pos) = rest.find("{{") {
+        let r = &rest[pos + 2..];
+        if rest[..pos].ends_with('\\') {
+            rest = r;
+            continue;
+        }
+        let Some(end) = r.find("}}") else {
+            break;
+        };
+        let name = r[..end].trim().to_string();
+        if !output.contains(&name) {
+            output.push(name);
+        }
+        rest = &r[end + 2..];
+    }
+    output
+}
+
+/// Prompt for the value of every `{{name}}` placeholder in the texts, e.g. the urls of `parse`.
+pub fn prompt_vars(theme: &ColorfulTheme, texts: &[&str]) -> Result<HashMap<String, String>> {
+    let mut vars = HashMap::new();
+    for text in texts {
+        for name in template_placeholders(text) {
+            if let Entry::Vacant(entry) = vars.entry(name) {
+                let value = Input::<String>::with_theme(theme)
+                    .with_prompt(format!("Value of {{{{{}}}}}", entry.key()))
+                    .interact_text()?;
+                entry.insert(value);
+            }
+        }
+    }
+    Ok(vars)
+}
+
+fn interpolate_str(s: &str) -> Result<String> {
+    let mut output = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(pos) = rest.find('$') {
+        output.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        if let Some(r) = rest.strip_prefix("$${") {
+            output.push_str("${");
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("${") {
+            let end = r
+                .find('}')
+                .ok_or_else(|| anyhow!("unterminated variable in `{}`", s))?;
+            let expr = &r[..end];
+            let (name, default) = match expr.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (expr, None),
+            };
+            match (std::env::var(name), default) {
+                (Ok(v), _) => output.push_str(&v),
+                (Err(_), Some(default)) => output.push_str(default),
+                (Err(_), None) => {
+                    return Err(anyhow!("environment variable `{}` is not set", name))
+                }
+            }
+            rest = &r[end + 1..];
+        } else {
+            output.push('$');
+            rest = &rest[1..];
+        }
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolate_str_should_work() {
+        std::env::set_var("XDIFF_TEST_HOST", "example.com");
+        std::env::remove_var("XDIFF_TEST_MISSING");
+
+        assert_eq!(
+            interpolate_str("https://${XDIFF_TEST_HOST}/todos").unwrap(),
+            "https://example.com/todos"
+        );
+        assert_eq!(
+            interpolate_str("${XDIFF_TEST_MISSING:-localhost}:$${PORT}$").unwrap(),
+            "localhost:${PORT}$"
+        );
+        assert!(interpolate_str("${XDIFF_TEST_HOST").is_err());
+    }
+
+    #[test]
+    fn render_template_should_work() {
+        let vars: HashMap<String, String> = [("id".to_string(), "1".to_string())].into();
+
+        assert_eq!(render_template("/todos/{{id}}", &vars).unwrap(), "/todos/1");
+        assert_eq!(
+            render_template("/todos/{{ id }}?a={{id}}", &vars).unwrap(),
+            "/todos/1?a=1"
+        );
+        assert_eq!(
+            render_template("/users/{{user}}", &vars)
+                .unwrap_err()
+                .to_string(),
+            "unresolved placeholder `{{user}}`"
+        );
+        assert_eq!(template_placeholders("{{a}}/{{ b }}/{{a}}"), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn render_template_should_keep_escaped_braces() {
+        let vars: HashMap<String, String> = [("id".to_string(), "1".to_string())].into();
+
+        assert_eq!(
+            render_template(r#"{"tpl": "\{{id}}", "id": {{id}}}"#, &vars).unwrap(),
+            r#"{"tpl": "{{id}}", "id": 1}"#
+        );
+        assert_eq!(render_template(r"\{{", &vars).unwrap(), "{{");
+        assert_eq!(template_placeholders(r"\{{a}}/{{b}}"), vec!["b"]);
+    }
+
+    #[test]
+    fn interpolate_vars_should_work() {
+        let mut config: Value = serde_yaml::from_str(
+            "vars:\n  id: 1\n  user: bob\ntodo:\n  url: http://localhost/todos/{{id}}\n  params:\n    user: '{{user}}'\n",
+        )
+        .unwrap();
+
+        interpolate_vars(&mut config, &[("id".into(), "2".into())]).unwrap();
+        let expected: Value = serde_yaml::from_str(
+            "todo:\n  url: http://localhost/todos/2\n  params:\n    user: bob\n",
+        )
+        .unwrap();
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn interpolate_env_with_missing_var_should_fail() {
+        std::env::remove_var("XDIFF_TEST_TOKEN");
+        let mut config: Value = serde_yaml::from_str(
+            "todo:\n  req1:\n    url: http://localhost\n    headers:\n      authorization: Bearer ${XDIFF_TEST_TOKEN}\n",
+        )
+        .unwrap();
+
+        let err = interpolate_env(&mut config).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "failed to validate profile: todo: req1.headers.authorization: environment variable `XDIFF_TEST_TOKEN` is not set"
+        );
+    }
+}
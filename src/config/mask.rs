@@ -0,0 +1,284 @@
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::ValidateConfig;
+use crate::JsonPath;
+
+/// A rule replacing the volatile part of header values and the body, e.g. a request id
+/// or a timestamp, so that only that part is hidden from the diff
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "MaskDef", into = "MaskDef")]
+pub struct Mask {
+    pub pattern: MaskPattern,
+    /// `<uuid>` style for built-in masks and `***` for a regex by default, `$1` refers to
+    /// a capture group
+    pub replacement: Option<String>,
+    /// only mask string values at this JSON path of JSON and YAML bodies
+    pub path: Option<String>,
+    /// the pattern, compiled once when the mask is created
+    regex: Regex,
+}
+
+/// A built-in mask, or a regex
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MaskPattern {
+    Builtin(BuiltinMask),
+    Regex(String),
+}
+
+/// Masks for values which change on every response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BuiltinMask {
+    /// e.g. `9b2e4a9c-0d3f-4c8e-9a51-2f1c0e7d6b3a`
+    Uuid,
+    /// ISO-8601 date and time, e.g. `2023-07-14T09:30:00.123Z`
+    Datetime,
+    /// hex digests of 32 characters or more, e.g. md5, sha1 or sha256
+    Hash,
+}
+
+/// a mask is either just a regex, or a map with a `builtin` mask or a regex `pattern`, and
+/// the options. A bare string is always a regex, so `uuid` matches the word uuid
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum MaskDef {
+    Regex(String),
+    Rule {
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        builtin: Option<BuiltinMask>,
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        pattern: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        replacement: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        path: Option<String>,
+    },
+}
+
+impl TryFrom<MaskDef> for Mask {
+    type Error = anyhow::Error;
+
+    fn try_from(def: MaskDef) -> Result<Self> {
+        match def {
+            MaskDef::Regex(regex) => Self::new(MaskPattern::Regex(regex), None, None),
+            MaskDef::Rule {
+                builtin,
+                pattern,
+                replacement,
+                path,
+            } => {
+                let pattern = match (builtin, pattern) {
+                    (Some(builtin), None) => MaskPattern::Builtin(builtin),
+                    (None, Some(regex)) => MaskPattern::Regex(regex),
+                    _ => return Err(anyhow!("a mask needs either `builtin` or `pattern`")),
+                };
+                Self::new(pattern, replacement, path)
+            }
+        }
+    }
+}
+
+impl From<Mask> for MaskDef {
+    fn from(mask: Mask) -> Self {
+        let (builtin, pattern) = match mask.pattern {
+            MaskPattern::Regex(regex) if mask.replacement.is_none() && mask.path.is_none() => {
+                return MaskDef::Regex(regex)
+            }
+            MaskPattern::Builtin(builtin) => (Some(builtin), None),
+            MaskPattern::Regex(regex) => (None, Some(regex)),
+        };
+        MaskDef::Rule {
+            builtin,
+            pattern,
+            replacement: mask.replacement,
+            path: mask.path,
+        }
+    }
+}
+
+impl PartialEq for Mask {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern
+            && self.replacement == other.replacement
+            && self.path == other.path
+    }
+}
+
+impl Eq for Mask {}
+
+impl Mask {
+    /// a mask with its pattern compiled, fails for an invalid regex
+    pub fn new(
+        pattern: MaskPattern,
+        replacement: Option<String>,
+        path: Option<String>,
+    ) -> Result<Self> {
+        let regex = match &pattern {
+            MaskPattern::Builtin(BuiltinMask::Uuid) => {
+                r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b"
+            }
+            MaskPattern::Builtin(BuiltinMask::Datetime) => {
+                r"\b\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}(:\d{2}(\.\d+)?)?(Z|[+-]\d{2}:?\d{2})?"
+            }
+            MaskPattern::Builtin(BuiltinMask::Hash) => r"(?i)\b[0-9a-f]{32,}\b",
+            MaskPattern::Regex(regex) => regex,
+        };
+        let regex =
+            Regex::new(regex).with_context(|| format!("invalid mask pattern: {}", regex))?;
+        Ok(Self {
+            pattern,
+            replacement,
+            path,
+            regex,
+        })
+    }
+
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    pub fn replacement(&self) -> &str {
+        match (&self.replacement, &self.pattern) {
+            (Some(replacement), _) => replacement,
+            (None, MaskPattern::Builtin(BuiltinMask::Uuid)) => "<uuid>",
+            (None, MaskPattern::Builtin(BuiltinMask::Datetime)) => "<datetime>",
+            (None, MaskPattern::Builtin(BuiltinMask::Hash)) => "<hash>",
+            (None, MaskPattern::Regex(_)) => "***",
+        }
+    }
+}
+
+/// apply the masks without a path to the text
+pub(crate) fn mask_text(masks: &[Mask], text: &str) -> String {
+    let mut text = text.to_string();
+    for mask in masks.iter().filter(|mask| mask.path.is_none()) {
+        text = mask
+            .regex()
+            .replace_all(&text, mask.replacement())
+            .into_owned();
+    }
+    text
+}
+
+/// apply the masks to the string values, only to those under the path of a mask with one, so
+/// the json stays valid. Paths that match nothing are added to the warnings, they are not fatal
+pub(crate) fn mask_json(
+    masks: &[Mask],
+    json: &mut Value,
+    warnings: &mut Vec<String>,
+) -> Result<()> {
+    for mask in masks {
+        let (path, json_path) = match &mask.path {
+            Some(path) => (path, path.parse::<JsonPath>()?),
+            None => {
+                mask_strings(json, mask.regex(), mask.replacement());
+                continue;
+            }
+        };
+        let values = json_path.select_mut(json);
+        if values.is_empty() {
+            warnings.push(format!("mask path `{}` not found in response body", path));
+        }
+        for value in values {
+            mask_strings(value, mask.regex(), mask.replacement());
+        }
+    }
+    Ok(())
+}
+
+/// masks with a path only apply to JSON and YAML bodies, warn about them for other bodies
+pub(crate) fn warn_path_masks(masks: &[Mask], format: &str, warnings: &mut Vec<String>) {
+    for path in masks.iter().filter_map(|mask| mask.path.as_ref()) {
+        warnings.push(format!(
+            "mask path `{}` ignored, paths only apply to json and yaml bodies but got {}",
+            path, format
+        ));
+    }
+}
+
+fn mask_strings(value: &mut Value, regex: &Regex, replacement: &str) {
+    match value {
+        Value::String(s) => *s = regex.replace_all(s, replacement).into_owned(),
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|v| mask_strings(v, regex, replacement)),
+        Value::Object(values) => values
+            .values_mut()
+            .for_each(|v| mask_strings(v, regex, replacement)),
+        _ => {}
+    }
+}
+
+impl ValidateConfig for Mask {
+    fn validate(&self) -> Result<()> {
+        if let Some(path) = &self.path {
+            path.parse::<JsonPath>()
+                .context(format!("invalid mask path: {}", path))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn mask_should_parse_names_regexes_and_rules() {
+        let yaml = "- builtin: uuid\n- req-\\d+\n- builtin: hash\n  replacement: <sha>\n  path: items[*].etag\n- uuid\n";
+        let masks: Vec<Mask> = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(masks[0].pattern, MaskPattern::Builtin(BuiltinMask::Uuid));
+        assert_eq!(masks[1].pattern, MaskPattern::Regex("req-\\d+".into()));
+        assert_eq!(masks[1].replacement(), "***");
+        assert_eq!(masks[2].replacement(), "<sha>");
+        assert_eq!(masks[2].path.as_deref(), Some("items[*].etag"));
+        // a bare string is a regex, even the name of a built-in mask
+        assert_eq!(masks[3].pattern, MaskPattern::Regex("uuid".into()));
+        assert_eq!(serde_yaml::to_string(&masks).unwrap(), yaml);
+
+        assert!(serde_yaml::from_str::<Mask>("'(unclosed'").is_err());
+        assert!(serde_yaml::from_str::<Mask>("builtin: uuid\npattern: a").is_err());
+    }
+
+    #[test]
+    fn mask_text_should_replace_volatile_parts() {
+        let masks: Vec<Mask> =
+            serde_yaml::from_str("- builtin: uuid\n- builtin: datetime\n- builtin: hash\n")
+                .unwrap();
+        let text = "/todo/9B2E4A9C-0D3F-4C8E-9A51-2F1C0E7D6B3A at 2023-07-14T09:30:00.123+02:00, \
+            etag d41d8cd98f00b204e9800998ecf8427e";
+        assert_eq!(
+            mask_text(&masks, text),
+            "/todo/<uuid> at <datetime>, etag <hash>"
+        );
+    }
+
+    #[test]
+    fn mask_json_should_only_touch_the_path() {
+        let masks: Vec<Mask> =
+            serde_yaml::from_str("- pattern: '\\d+'\n  replacement: N\n  path: items[*]\n")
+                .unwrap();
+        let mut json = json!({"id": "a1", "items": [{"url": "/a/12"}, "b34", 5]});
+        mask_json(&masks, &mut json, &mut vec![]).unwrap();
+        assert_eq!(
+            json,
+            json!({"id": "a1", "items": [{"url": "/a/N"}, "bN", 5]})
+        );
+    }
+
+    #[test]
+    fn mask_json_should_keep_the_json_valid() {
+        // would match `"n": 12` in the pretty-printed text, only strings are masked
+        let masks: Vec<Mask> = serde_yaml::from_str("- ': \\d+'\n").unwrap();
+        let mut json = json!({"n": 12, "note": "took: 12ms", "items": ["a: 1"]});
+        mask_json(&masks, &mut json, &mut vec![]).unwrap();
+        assert_eq!(
+            json,
+            json!({"n": 12, "note": "took***ms", "items": ["a***"]})
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::header::HeaderValue;
+use serde_json::Value;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::path::Path;
+
+/// content type of a file part without `type=`
+const DEFAULT_FILE_TYPE: &str = "application/octet-stream";
+
+/// A file part, declared as `@path/to/file;filename=name.png;type=image/png`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FilePart<'a> {
+    path: &'a str,
+    filename: Option<&'a str>,
+    content_type: Option<&'a str>,
+}
+
+/// Encode the fields of the body as `multipart/form-data`, returns the content type with the
+/// generated boundary. A string starting with `@` is the path of a file to upload, unless it
+/// starts with `@@` which is sent as text with a single `@`. An array is sent as one part per
+/// item, anything else as a text part
+pub(crate) async fn encode(body: &Value) -> Result<(HeaderValue, Vec<u8>)> {
+    let hasher = RandomState::new().build_hasher();
+    let boundary = format!("xdiff-boundary-{:016x}", hasher.finish());
+    let content_type = format!("multipart/form-data; boundary={}", boundary);
+    Ok((
+        HeaderValue::from_str(&content_type)?,
+        encode_with_boundary(body, &boundary).await?,
+    ))
+}
+
+async fn encode_with_boundary(body: &Value, boundary: &str) -> Result<Vec<u8>> {
+    let fields = body
+        .as_object()
+        .ok_or_else(|| anyhow!("multipart body must be an object"))?;
+
+    let mut output = vec![];
+    for (name, value) in fields {
+        let values = match value {
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            output.extend(format!("--{}\r\n", boundary).as_bytes());
+            write_part(&mut output, name, value).await?;
+            output.extend(b"\r\n");
+        }
+    }
+    output.extend(format!("--{}--\r\n", boundary).as_bytes());
+    Ok(output)
+}
+
+async fn write_part(output: &mut Vec<u8>, name: &str, value: &Value) -> Result<()> {
+    let disposition = format!("Content-Disposition: form-data; name=\"{}\"", escape(name));
+    let text = match value {
+        // `@@` escapes a text starting with `@`
+        Value::String(s) if s.starts_with("@@") => s[1..].to_string(),
+        Value::String(s) => match s.strip_prefix('@') {
+            Some(spec) => {
+                let file = FilePart::parse(spec);
+                let content = tokio::fs::read(file.path)
+                    .await
+                    .with_context(|| format!("failed to read multipart file {}", file.path))?;
+                let filename = file.filename.unwrap_or_else(|| {
+                    Path::new(file.path)
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .unwrap_or(file.path)
+                });
+                output.extend(
+                    format!(
+                        "{}; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+                        disposition,
+                        escape(filename),
+                        file.content_type.unwrap_or(DEFAULT_FILE_TYPE)
+                    )
+                    .as_bytes(),
+                );
+                output.extend(content);
+                return Ok(());
+            }
+            None => s.clone(),
+        },
+        Value::Null => String::new(),
+        Value::Bool(_) | Value::Number(_) => value.to_string(),
+        Value::Array(_) | Value::Object(_) => serde_json::to_string(value)?,
+    };
+    output.extend(format!("{}\r\n\r\n{}", disposition, text).as_bytes());
+    Ok(())
+}
+
+impl<'a> FilePart<'a> {
+    fn parse(spec: &'a str) -> Self {
+        let mut items = spec.split(';');
+        let mut part = Self {
+            path: items.next().unwrap_or_default(),
+            filename: None,
+            content_type: None,
+        };
+        for item in items {
+            match item.split_once('=') {
+                Some(("filename", v)) => part.filename = Some(v),
+                Some(("type", v)) => part.content_type = Some(v),
+                _ => {}
+            }
+        }
+        part
+    }
+}
+
+/// quotes and line breaks can not appear in a quoted header parameter
+fn escape(s: &str) -> String {
+    s.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn encode_should_write_text_and_file_parts() {
+        let path = std::env::temp_dir().join("xdiff-multipart-test.txt");
+        std::fs::write(&path, "hello").unwrap();
+        let body = json!({
+            "title": "todo",
+            "tags": ["a", 1],
+            "file": format!("@{};type=text/plain", path.display()),
+        });
+
+        let output = encode_with_boundary(&body, "b").await.unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            [
+                "--b",
+                "Content-Disposition: form-data; name=\"file\"; filename=\"xdiff-multipart-test.txt\"",
+                "Content-Type: text/plain",
+                "",
+                "hello",
+                "--b",
+                "Content-Disposition: form-data; name=\"tags\"",
+                "",
+                "a",
+                "--b",
+                "Content-Disposition: form-data; name=\"tags\"",
+                "",
+                "1",
+                "--b",
+                "Content-Disposition: form-data; name=\"title\"",
+                "",
+                "todo",
+                "--b--",
+                "",
+            ]
+            .join("\r\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn encode_should_unescape_text_starting_with_at() {
+        let body = json!({"handle": "@@me"});
+        let output = encode_with_boundary(&body, "b").await.unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "--b\r\nContent-Disposition: form-data; name=\"handle\"\r\n\r\n@me\r\n--b--\r\n"
+        );
+    }
+
+    #[test]
+    fn file_part_should_parse_options() {
+        assert_eq!(
+            FilePart::parse("a/b.png;filename=c.png;type=image/png"),
+            FilePart {
+                path: "a/b.png",
+                filename: Some("c.png"),
+                content_type: Some("image/png"),
+            }
+        );
+    }
+}
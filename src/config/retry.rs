@@ -0,0 +1,204 @@
+use anyhow::{anyhow, Result};
+use reqwest::{header, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use super::ValidateConfig;
+
+/// When and how often a request is sent again
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RetryPolicy {
+    /// attempts in total, including the first one
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// delay before the first retry, doubled for each following retry
+    #[serde(with = "humantime_serde", default = "default_backoff")]
+    pub backoff: Duration,
+    /// response statuses which are retried, timeouts and connection errors always are
+    #[serde(default = "default_on_status")]
+    pub on_status: Vec<u16>,
+    /// longest wait before a retry, the request `timeout` or one minute if not given
+    #[serde(
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub max_delay: Option<Duration>,
+}
+
+/// longest wait before a retry without a `max_delay` or a request timeout
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// A failed attempt which was retried
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Retry {
+    /// 1-based number of the failed attempt
+    pub attempt: u32,
+    /// status of the response, or the network error
+    pub cause: String,
+    /// time waited before the next attempt
+    pub delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            backoff: default_backoff(),
+            on_status: default_on_status(),
+            max_delay: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// a single attempt, used when the profile has no `retry`
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// the cause if the response is retried
+    pub(crate) fn retry_response(&self, res: &Response) -> Option<String> {
+        self.on_status
+            .contains(&res.status().as_u16())
+            .then(|| res.status().to_string())
+    }
+
+    /// the cause if the error is retried, only timeouts and connection errors are
+    pub(crate) fn retry_error(&self, e: &anyhow::Error) -> Option<String> {
+        let transient = e
+            .chain()
+            .filter_map(|e| e.downcast_ref::<reqwest::Error>())
+            .any(|e| e.is_timeout() || e.is_connect());
+        transient.then(|| format!("{:#}", e))
+    }
+
+    /// `Retry-After` of a 429 or 503 response, or the exponential backoff, at most
+    /// `max_delay`, or else the request `timeout`
+    pub(crate) fn delay(
+        &self,
+        attempt: u32,
+        res: Option<&Response>,
+        timeout: Option<Duration>,
+    ) -> Duration {
+        let retry_after = res
+            .filter(|res| {
+                matches!(
+                    res.status(),
+                    StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+                )
+            })
+            .and_then(|res| res.headers().get(header::RETRY_AFTER))
+            .and_then(|v| v.to_str().ok());
+        self.delay_after(attempt, retry_after, timeout, SystemTime::now())
+    }
+
+    fn delay_after(
+        &self,
+        attempt: u32,
+        retry_after: Option<&str>,
+        timeout: Option<Duration>,
+        now: SystemTime,
+    ) -> Duration {
+        let delay = match retry_after.and_then(|v| parse_retry_after(v, now)) {
+            Some(delay) => delay,
+            None => self
+                .backoff
+                .saturating_mul(2u32.saturating_pow(attempt - 1)),
+        };
+        delay.min(self.max_delay.or(timeout).unwrap_or(MAX_DELAY))
+    }
+}
+
+impl ValidateConfig for RetryPolicy {
+    fn validate(&self) -> Result<()> {
+        if self.max_attempts == 0 {
+            return Err(anyhow!("retry max_attempts must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Retry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "attempt {} failed with {}, retrying in {}ms",
+            self.attempt, self.cause, self.delay_ms
+        )
+    }
+}
+
+/// `Retry-After` is either seconds or an http date, a date in the past means no delay
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_backoff() -> Duration {
+    Duration::from_millis(500)
+}
+
+fn default_on_status() -> Vec<u16> {
+    vec![429, 502, 503, 504]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_policy_delay_should_use_retry_after() {
+        let policy = RetryPolicy::default();
+        let now = httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+        let delay = |retry_after| policy.delay_after(1, retry_after, None, now);
+
+        assert_eq!(delay(Some(" 3 ")), Duration::from_secs(3));
+        assert_eq!(
+            delay(Some("Wed, 21 Oct 2015 07:28:30 GMT")),
+            Duration::from_secs(30)
+        );
+        assert_eq!(delay(Some("Wed, 21 Oct 2015 07:00:00 GMT")), Duration::ZERO);
+        // falls back to the backoff
+        assert_eq!(delay(Some("soon")), Duration::from_millis(500));
+        assert_eq!(delay(None), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn retry_policy_delay_should_be_capped() {
+        let mut policy = RetryPolicy::default();
+        let now = SystemTime::now();
+        let delay =
+            |policy: &RetryPolicy, timeout| policy.delay_after(1, Some("86400"), timeout, now);
+
+        assert_eq!(delay(&policy, None), MAX_DELAY);
+        assert_eq!(
+            delay(&policy, Some(Duration::from_secs(10))),
+            Duration::from_secs(10)
+        );
+        policy.max_delay = Some(Duration::from_secs(5));
+        assert_eq!(
+            delay(&policy, Some(Duration::from_secs(10))),
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            policy.delay_after(12, None, None, now),
+            Duration::from_secs(5)
+        );
+
+        let policy: RetryPolicy = serde_yaml::from_str("max_delay: 2s").unwrap();
+        assert_eq!(policy.max_delay, Some(Duration::from_secs(2)));
+    }
+}
@@ -0,0 +1,160 @@
+use anyhow::{anyhow, Context, Result};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncReadExt;
+
+use super::ConfigFormat;
+use crate::InvalidConfig;
+
+/// Where the config is read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// `-c -`
+    Stdin,
+    File(PathBuf),
+}
+
+impl ConfigSource {
+    /// Find the config of `tool` (`xdiff` or `xreq`). The lookup order is:
+    ///
+    /// 1. `--config`, `-` for stdin
+    /// 2. the `XDIFF_CONFIG` / `XREQ_CONFIG` env var
+    /// 3. `./xdiff.yml` / `./xreq.yml`, walking up the parent directories
+    /// 4. `$XDG_CONFIG_HOME/xdiff/`, which defaults to `~/.config/xdiff/`
+    pub fn locate(config: Option<&str>, tool: &str) -> Result<Self> {
+        let cwd = std::env::current_dir()?;
+        Self::locate_in(config, tool, &cwd, |key| std::env::var(key).ok())
+            .map_err(|e| InvalidConfig(e).into())
+    }
+
+    fn locate_in(
+        config: Option<&str>,
+        tool: &str,
+        cwd: &Path,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        match config {
+            Some("-") => return Ok(Self::Stdin),
+            Some(path) => return Ok(Self::File(path.into())),
+            None => {}
+        }
+
+        let env_key = format!("{}_CONFIG", tool.to_uppercase());
+        if let Some(path) = env(&env_key).filter(|v| !v.is_empty()) {
+            return Ok(Self::File(path.into()));
+        }
+
+        let file_name = format!("{}.yml", tool);
+        let mut tried = vec![];
+        for dir in cwd.ancestors() {
+            tried.push(dir.join(&file_name));
+        }
+        let config_home = env("XDG_CONFIG_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env("HOME").map(|home| Path::new(&home).join(".config")));
+        if let Some(config_home) = config_home {
+            tried.push(config_home.join("xdiff").join(&file_name));
+        }
+
+        if let Some(path) = tried.iter().find(|path| path.is_file()) {
+            return Ok(Self::File(path.clone()));
+        }
+
+        let mut locations = vec![
+            "--config (not given)".to_string(),
+            format!("{} (not set)", env_key),
+        ];
+        locations.extend(tried.iter().map(|path| path.display().to_string()));
+        Err(anyhow!(
+            "no config file found, tried:\n  {}",
+            locations.join("\n  ")
+        ))
+    }
+
+    /// path of the config file, none for stdin
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Stdin => None,
+            Self::File(path) => Some(path),
+        }
+    }
+
+    /// format of the config detected from the file extension, yaml for stdin
+    pub fn format(&self) -> ConfigFormat {
+        match self {
+            Self::Stdin => ConfigFormat::Yaml,
+            Self::File(path) => ConfigFormat::from_path(path),
+        }
+    }
+
+    /// read the content of the config
+    pub async fn read(&self) -> Result<String> {
+        let content = match self {
+            Self::Stdin => {
+                let mut content = String::new();
+                tokio::io::stdin()
+                    .read_to_string(&mut content)
+                    .await
+                    .map(|_| content)
+                    .context("failed to read config from stdin")
+            }
+            Self::File(path) => tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("failed to read config file {}", path.display())),
+        };
+        content.map_err(|e| InvalidConfig(e).into())
+    }
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdin => write!(f, "<stdin>"),
+            Self::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate_config_should_follow_lookup_order() {
+        let no_env = |_: &str| None;
+        let cwd = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+
+        assert_eq!(
+            ConfigSource::locate_in(Some("-"), "xdiff", &cwd, no_env).unwrap(),
+            ConfigSource::Stdin
+        );
+        assert_eq!(
+            ConfigSource::locate_in(Some("a.yml"), "xdiff", &cwd, no_env).unwrap(),
+            ConfigSource::File("a.yml".into())
+        );
+
+        let env = |key: &str| (key == "XREQ_CONFIG").then(|| "b.yml".to_string());
+        assert_eq!(
+            ConfigSource::locate_in(None, "xreq", &cwd, env).unwrap(),
+            ConfigSource::File("b.yml".into())
+        );
+
+        // fixtures/test.yml is found from a sub directory of fixtures
+        let sub_dir = cwd.join("missing");
+        assert_eq!(
+            ConfigSource::locate_in(None, "test", &sub_dir, no_env).unwrap(),
+            ConfigSource::File(cwd.join("test.yml"))
+        );
+
+        let config_home =
+            |key: &str| (key == "XDG_CONFIG_HOME").then(|| "/nonexistent".to_string());
+        let err = ConfigSource::locate_in(None, "xdiff", &sub_dir, config_home).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.starts_with(
+            "no config file found, tried:\n  --config (not given)\n  XDIFF_CONFIG (not set)\n"
+        ));
+        assert!(msg.contains(&sub_dir.join("xdiff.yml").display().to_string()));
+        assert!(msg.ends_with("/nonexistent/xdiff/xdiff.yml"));
+    }
+}
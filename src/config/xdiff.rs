@@ -1,9 +1,16 @@
-use crate::{diff_text, ExtraArgs, RequestProfile};
+use crate::{
+    diff_hunks, diff_json, diff_text, diff_text_side_by_side, json_hunks, terminal_width,
+    DiffReport, ExtraArgs, Hunk, JsonPath, RequestProfile, ResponseReport, Retry,
+};
 use anyhow::{Context, Result};
+use clap::ValueEnum;
+use console::style;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
-use super::{is_default, LoadConfig, ValidateConfig};
+use super::body::print_warnings;
+use super::{binary, is_default, BodyFormat, LoadConfig, Mask, ResponseText, ValidateConfig};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct DiffConfig {
@@ -21,6 +28,30 @@ pub struct DiffProfile {
     pub req2: RequestProfile,
     #[serde(skip_serializing_if = "is_default", default)]
     pub res: ResponseProfile,
+    /// send req2 only after req1 finished, for endpoints that can not take parallel calls
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub sequential: bool,
+}
+
+/// How the line diff is laid out
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// old and new lines interleaved in one column
+    #[default]
+    Inline,
+    /// old and new lines aligned in two columns fitted to the terminal width
+    SideBySide,
+}
+
+/// Rendered diff of the two responses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffOutput {
+    pub text: String,
+    /// whether the responses differ after the response profile is applied
+    pub changed: bool,
+    /// failed attempts of req1 and req2 which were retried
+    pub retries: [Vec<Retry>; 2],
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Default)]
@@ -29,6 +60,27 @@ pub struct ResponseProfile {
     pub skip_headers: Vec<String>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub skip_body: Vec<String>,
+    /// normalize bodies in this format instead of the one of their content type
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub body_format: Option<BodyFormat>,
+    /// hide volatile parts of header values and bodies, e.g. uuids and timestamps
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub mask: Vec<Mask>,
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub diff_mode: DiffMode,
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub layout: Layout,
+}
+
+/// How the response bodies are compared
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffMode {
+    /// pretty-print both bodies and diff them line by line
+    #[default]
+    Line,
+    /// walk both JSON bodies and report changed values by JSON path
+    Json,
 }
 
 impl ResponseProfile {
@@ -36,6 +88,10 @@ impl ResponseProfile {
         Self {
             skip_headers,
             skip_body,
+            body_format: None,
+            mask: vec![],
+            diff_mode: DiffMode::default(),
+            layout: Layout::default(),
         }
     }
 }
@@ -56,26 +112,185 @@ impl DiffConfig {
 
 impl DiffProfile {
     pub fn new(req1: RequestProfile, req2: RequestProfile, res: ResponseProfile) -> Self {
-        Self { req1, req2, res }
+        Self {
+            req1,
+            req2,
+            res,
+            sequential: false,
+        }
     }
 
-    pub async fn diff(&self, args: ExtraArgs) -> Result<String> {
-        let res1 = self.req1.send(&args).await?;
-        let res2 = self.req2.send(&args).await?;
+    /// the profile with inline credentials of both requests masked, for displaying it
+    pub fn masked(&self) -> Self {
+        Self {
+            req1: self.req1.masked(),
+            req2: self.req2.masked(),
+            ..self.clone()
+        }
+    }
+
+    /// the profile with the extra args applied to both requests
+    pub fn resolve(&self, args: &ExtraArgs) -> Result<Self> {
+        Ok(Self {
+            req1: self.req1.resolve(args)?,
+            req2: self.req2.resolve(args)?,
+            ..self.clone()
+        })
+    }
+
+    pub async fn diff(&self, args: ExtraArgs) -> Result<DiffOutput> {
+        let [(text1, elapsed1), (text2, elapsed2)] = self.fetch_both(&args).await?;
 
-        let text1 = res1.get_text(&self.res).await?;
-        let text2 = res2.get_text(&self.res).await?;
+        let mut output = format_stats("req1", &text1, elapsed1);
+        output.push_str(&format_stats("req2", &text2, elapsed2));
+
+        match self.res.diff_mode {
+            DiffMode::Line => {
+                output.push_str(&self.diff_lines(&full_text(&text1), &full_text(&text2))?);
+            }
+            DiffMode::Json => {
+                output.push_str(&self.diff_lines(&text1.head, &text2.head)?);
+                // fall back to line diff if either side is not a json body
+                match (json_body(&text1), json_body(&text2)) {
+                    (Some(v1), Some(v2)) => output.push_str(&diff_json(&v1, &v2)?),
+                    _ => output.push_str(&self.diff_lines(&text1.body, &text2.body)?),
+                }
+            }
+        }
+        // binary bodies are diffed by their summary, the bytes where they differ are shown
+        if let (Some(bytes1), Some(bytes2)) = (&text1.binary, &text2.binary) {
+            if let Some(hex) = binary::hex_diff(bytes1, bytes2) {
+                output.push_str(&hex);
+            }
+        }
 
-        diff_text(&text1, &text2)
+        Ok(DiffOutput {
+            text: output,
+            changed: self.changed(&text1, &text2),
+            retries: [text1.retries, text2.retries],
+        })
+    }
+
+    /// diff the responses into a machine-readable report
+    pub async fn report(&self, name: &str, args: &ExtraArgs) -> Result<DiffReport> {
+        let url1 = self.req1.get_url(args)?;
+        let url2 = self.req2.get_url(args)?;
+        let [(text1, elapsed1), (text2, elapsed2)] = self.fetch_both(args).await?;
+
+        Ok(DiffReport {
+            profile: name.to_string(),
+            changed: self.changed(&text1, &text2),
+            hunks: self.hunks(&text1, &text2),
+            req1: response_report(&self.req1, url1, text1, elapsed1),
+            req2: response_report(&self.req2, url2, text2, elapsed2),
+        })
+    }
+
+    /// line hunks, or in json mode the changes of json bodies, as `diff` shows them
+    fn hunks(&self, text1: &ResponseText, text2: &ResponseText) -> Vec<Hunk> {
+        match (self.res.diff_mode, json_body(text1), json_body(text2)) {
+            (DiffMode::Json, Some(v1), Some(v2)) => {
+                let mut hunks = diff_hunks(&text1.head, &text2.head);
+                hunks.extend(json_hunks(&v1, &v2));
+                hunks
+            }
+            (DiffMode::Json, _, _) => {
+                let mut hunks = diff_hunks(&text1.head, &text2.head);
+                hunks.extend(diff_hunks(&text1.body, &text2.body));
+                hunks
+            }
+            (DiffMode::Line, _, _) => diff_hunks(&full_text(text1), &full_text(text2)),
+        }
+    }
+
+    /// send both requests, concurrently unless the profile is sequential
+    async fn fetch_both(&self, args: &ExtraArgs) -> Result<[(ResponseText, Duration); 2]> {
+        let (res1, res2) = if self.sequential {
+            let res1 = self.fetch(&self.req1, args).await;
+            let res2 = self.fetch(&self.req2, args).await;
+            (res1, res2)
+        } else {
+            tokio::join!(self.fetch(&self.req1, args), self.fetch(&self.req2, args))
+        };
+        let (res1, res2) = (res1?, res2?);
+        let warnings = [&res1.0.warnings[..], &res2.0.warnings[..]].concat();
+        print_warnings(&warnings);
+        Ok([res1, res2])
+    }
+
+    fn diff_lines(&self, text1: &str, text2: &str) -> Result<String> {
+        match self.res.layout {
+            Layout::Inline => diff_text(text1, text2),
+            Layout::SideBySide => diff_text_side_by_side(text1, text2, terminal_width()),
+        }
+    }
+
+    fn changed(&self, text1: &ResponseText, text2: &ResponseText) -> bool {
+        match self.res.diff_mode {
+            DiffMode::Line => full_text(text1) != full_text(text2),
+            DiffMode::Json => {
+                let body_changed = match (json_body(text1), json_body(text2)) {
+                    (Some(v1), Some(v2)) => v1 != v2,
+                    _ => text1.body != text2.body,
+                };
+                text1.head != text2.head || body_changed
+            }
+        }
+    }
+
+    /// send the request and read the response, returns the time it took
+    async fn fetch(
+        &self,
+        req: &RequestProfile,
+        args: &ExtraArgs,
+    ) -> Result<(ResponseText, Duration)> {
+        let start = Instant::now();
+        let text = req.send(args).await?.get_response_text(&self.res).await?;
+        Ok((text, start.elapsed()))
+    }
+}
+
+fn full_text(text: &ResponseText) -> String {
+    format!("{}{}\n", text.head, text.body)
+}
+
+fn json_body(text: &ResponseText) -> Option<serde_json::Value> {
+    serde_json::from_str(&text.body).ok()
+}
+
+fn response_report(
+    req: &RequestProfile,
+    url: String,
+    text: ResponseText,
+    elapsed: Duration,
+) -> ResponseReport {
+    ResponseReport {
+        method: req.method.to_string(),
+        url,
+        status: text.status.as_u16(),
+        headers: text.headers,
+        body: text.body,
+        size: text.size,
+        elapsed_ms: elapsed.as_millis() as u64,
+        retries: text.retries,
     }
 }
 
+fn format_stats(name: &str, text: &ResponseText, elapsed: Duration) -> String {
+    let line = format!(
+        "{}: {}, {} bytes, {}ms",
+        name,
+        text.status,
+        text.size,
+        elapsed.as_millis()
+    );
+    format!("{}\n", style(line).dim())
+}
+
 impl ValidateConfig for DiffConfig {
     fn validate(&self) -> Result<()> {
         for (name, profile) in &self.profiles {
-            profile
-                .validate()
-                .context(format!("failed to validate profile: {}", name))?;
+            profile.validate_profile(name)?;
         }
         Ok(())
     }
@@ -85,6 +300,20 @@ impl ValidateConfig for DiffProfile {
     fn validate(&self) -> Result<()> {
         self.req1.validate().context("req1 failed to validate")?;
         self.req2.validate().context("req2 failed to validate")?;
+        self.res.validate().context("res failed to validate")?;
+        Ok(())
+    }
+}
+
+impl ValidateConfig for ResponseProfile {
+    fn validate(&self) -> Result<()> {
+        for path in &self.skip_body {
+            path.parse::<JsonPath>()
+                .context(format!("invalid skip_body path: {}", path))?;
+        }
+        for mask in &self.mask {
+            mask.validate()?;
+        }
         Ok(())
     }
 }
@@ -1,7 +1,7 @@
 use std::{fmt::Debug, collections::HashMap};
 use serde::{Serialize, Deserialize};
 use crate::RequestProfile;
-use anyhow::{Result, Context};
+use anyhow::Result;
 
 use super::{ValidateConfig, LoadConfig};
 
@@ -26,9 +26,7 @@ impl RequestConfig {
 impl ValidateConfig for RequestConfig {
     fn validate(&self) -> Result<()> {
         for (name, profile) in &self.profiles {
-            profile
-                .validate()
-                .context(format!("failed to validate profile: {}", name))?;
+            profile.validate_profile(name)?;
         }
         Ok(())
     }
@@ -0,0 +1,283 @@
+use crate::{
+    highlight_html, json_hunks, DiffMode, DiffProfile, DiffReport, Hunk, LineTag, ResponseReport,
+};
+use anyhow::Result;
+use similar::{ChangeTag, DiffOp, TextDiff};
+use std::fmt::Write as _;
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; margin: 2em; color: #24292f; }
+h1 .changed { color: #cf222e; } h1 .same { color: #1a7f37; }
+table.summary { border-collapse: collapse; margin-bottom: 1em; }
+table.summary td, table.summary th { border: 1px solid #d0d7de; padding: 4px 8px; text-align: left; }
+.requests { display: flex; gap: 1em; } .requests > div { flex: 1; min-width: 0; overflow: auto; }
+table.diff { border-collapse: collapse; width: 100%; font-family: ui-monospace, Menlo, monospace; font-size: 12px; }
+table.diff td { padding: 0 6px; white-space: pre-wrap; word-break: break-all; vertical-align: top; }
+table.diff td.num { color: #8c959f; width: 3em; text-align: right; user-select: none; }
+table.diff td.sign { width: 1em; user-select: none; }
+tr.delete { background: #ffebe9; } tr.delete mark { background: #ff8182; }
+tr.insert { background: #e6ffec; } tr.insert mark { background: #abf2bc; }
+tr.equal { color: #57606a; }
+details.unchanged summary { cursor: pointer; color: #0969da; font-size: 12px; padding: 2px 6px; background: #f6f8fa; }
+"#;
+
+/// Render the report as a self-contained html page, `profile` is the one diffed, resolved
+/// with the extra args and masked, see `DiffProfile::resolve`
+pub fn html_report(report: &DiffReport, profile: &DiffProfile) -> Result<String> {
+    let mut output = String::new();
+
+    let (class, result) = match report.changed {
+        true => ("changed", "responses differ"),
+        false => ("same", "responses are identical"),
+    };
+    writeln!(
+        &mut output,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>xdiff: {}</title>\n<style>{}</style>\n</head>\n<body>",
+        escape_html(&report.profile),
+        STYLE
+    )?;
+    writeln!(
+        &mut output,
+        "<h1>{} <span class=\"{}\">{}</span></h1>",
+        escape_html(&report.profile),
+        class,
+        result
+    )?;
+
+    output.push_str("<table class=\"summary\">\n<tr><th></th><th>method</th><th>url</th><th>status</th><th>size</th><th>time</th></tr>\n");
+    for (name, res) in [("req1", &report.req1), ("req2", &report.req2)] {
+        writeln!(
+            &mut output,
+            "<tr><th>{}</th><td>{}</td><td>{}</td><td>{}</td><td>{} bytes</td><td>{}ms</td></tr>",
+            name,
+            escape_html(&res.method),
+            escape_html(&res.url),
+            res.status,
+            res.size,
+            res.elapsed_ms
+        )?;
+    }
+    output.push_str("</table>\n");
+
+    output.push_str("<h2>Requests</h2>\n<div class=\"requests\">\n");
+    for (name, req) in [("req1", &profile.req1), ("req2", &profile.req2)] {
+        let yaml = serde_yaml::to_string(req)?;
+        writeln!(
+            &mut output,
+            "<div><h3>{}</h3>\n{}</div>",
+            name,
+            highlight_html(&yaml, "yaml", None)?
+        )?;
+    }
+    output.push_str("</div>\n");
+
+    let (req1, req2) = (&report.req1, &report.req2);
+    let sections = [
+        ("Status", status_text(req1), status_text(req2)),
+        ("Headers", headers_text(req1), headers_text(req2)),
+    ];
+    for (title, text1, text2) in sections {
+        writeln!(&mut output, "<h2>{}</h2>", title)?;
+        output.push_str(&diff_html(&text1, &text2)?);
+    }
+    output.push_str("<h2>Body</h2>\n");
+    // json bodies are compared by their values in json mode, the same as the text output
+    let json = |res: &ResponseReport| serde_json::from_str::<serde_json::Value>(&res.body).ok();
+    match (profile.res.diff_mode, json(req1), json(req2)) {
+        (DiffMode::Json, Some(v1), Some(v2)) => {
+            output.push_str(&hunks_html(&json_hunks(&v1, &v2))?)
+        }
+        _ => output.push_str(&diff_html(&body_text(req1), &body_text(req2))?),
+    }
+
+    output.push_str("</body>\n</html>\n");
+    Ok(output)
+}
+
+fn status_text(res: &ResponseReport) -> String {
+    format!("{}\n", res.status)
+}
+
+fn headers_text(res: &ResponseReport) -> String {
+    res.headers
+        .iter()
+        .map(|(k, v)| format!("{}: {}\n", k, v))
+        .collect()
+}
+
+fn body_text(res: &ResponseReport) -> String {
+    format!("{}\n", res.body)
+}
+
+/// Render the line diff as html tables, grouped the same way as `diff_text`.
+/// Unchanged lines between the groups are collapsed.
+pub fn diff_html(text1: &str, text2: &str) -> Result<String> {
+    let mut output = String::new();
+
+    let diff = TextDiff::from_lines(text1, text2);
+    let old_lines: Vec<_> = diff.old_slices().to_vec();
+    let mut next_old = 0;
+    // offset from an old line index to the new one, which is constant in unchanged lines
+    let mut offset = 0isize;
+
+    for group in diff.grouped_ops(3) {
+        let (first, last) = (group[0], group[group.len() - 1]);
+        let start = first.old_range().start;
+        offset = first.new_range().start as isize - start as isize;
+        write_unchanged(&mut output, &old_lines, next_old..start, offset)?;
+
+        output.push_str("<table class=\"diff\">\n");
+        for op in &group {
+            write_op(&mut output, &diff, op)?;
+        }
+        output.push_str("</table>\n");
+
+        next_old = last.old_range().end;
+        offset = last.new_range().end as isize - next_old as isize;
+    }
+    write_unchanged(&mut output, &old_lines, next_old..old_lines.len(), offset)?;
+
+    Ok(output)
+}
+
+/// Render hunks, e.g. the changes of json bodies, as an html table
+fn hunks_html(hunks: &[Hunk]) -> Result<String> {
+    let mut output = String::new();
+    for hunk in hunks {
+        output.push_str("<table class=\"diff\">\n");
+        for line in &hunk.lines {
+            let (class, sign) = match line.tag {
+                LineTag::Delete => ("delete", "-"),
+                LineTag::Insert => ("insert", "+"),
+                LineTag::Equal => ("equal", " "),
+            };
+            let number = |n: Option<usize>| n.map(|n| n.to_string()).unwrap_or_default();
+            writeln!(
+                output,
+                "<tr class=\"{}\"><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"sign\">{}</td><td>{}</td></tr>",
+                class,
+                number(line.old_line),
+                number(line.new_line),
+                sign,
+                escape_html(&line.value)
+            )?;
+        }
+        output.push_str("</table>\n");
+    }
+    Ok(output)
+}
+
+fn write_op<'a>(
+    output: &mut String,
+    diff: &'a TextDiff<'a, 'a, 'a, str>,
+    op: &DiffOp,
+) -> Result<()> {
+    for change in diff.iter_inline_changes(op) {
+        let (class, sign) = match change.tag() {
+            ChangeTag::Delete => ("delete", "-"),
+            ChangeTag::Insert => ("insert", "+"),
+            ChangeTag::Equal => ("equal", " "),
+        };
+        write!(
+            output,
+            "<tr class=\"{}\"><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"sign\">{}</td><td>",
+            class,
+            line_number(change.old_index()),
+            line_number(change.new_index()),
+            sign
+        )?;
+        for (emphasized, value) in change.iter_strings_lossy() {
+            let value = escape_html(value.trim_end_matches(['\r', '\n']));
+            if emphasized {
+                write!(output, "<mark>{}</mark>", value)?;
+            } else {
+                output.push_str(&value);
+            }
+        }
+        output.push_str("</td></tr>\n");
+    }
+    Ok(())
+}
+
+fn write_unchanged(
+    output: &mut String,
+    lines: &[&str],
+    range: std::ops::Range<usize>,
+    offset: isize,
+) -> Result<()> {
+    if range.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(
+        output,
+        "<details class=\"unchanged\"><summary>{} unchanged lines</summary>\n<table class=\"diff\">",
+        range.len()
+    )?;
+    for i in range {
+        writeln!(
+            output,
+            "<tr class=\"equal\"><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"sign\"> </td><td>{}</td></tr>",
+            i + 1,
+            (i as isize + offset + 1),
+            escape_html(lines[i].trim_end_matches(['\r', '\n']))
+        )?;
+    }
+    output.push_str("</table></details>\n");
+    Ok(())
+}
+
+fn line_number(idx: Option<usize>) -> String {
+    idx.map(|i| (i + 1).to_string()).unwrap_or_default()
+}
+
+fn escape_html(s: &str) -> String {
+    let mut output = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_html_should_work() {
+        let text1 = "a\nb\nc\nd\ne\nf\ng\n<h>\n";
+        let text2 = "a\nb\nc\nd\ne\nf\ng\n<i>\n";
+
+        let output = diff_html(text1, text2).unwrap();
+        assert!(output
+            .starts_with("<details class=\"unchanged\"><summary>4 unchanged lines</summary>\n"));
+        assert!(output.contains("<tr class=\"delete\"><td class=\"num\">8</td><td class=\"num\"></td><td class=\"sign\">-</td><td><mark>&lt;h&gt;</mark></td></tr>"));
+        assert!(output.contains("<tr class=\"insert\"><td class=\"num\"></td><td class=\"num\">8</td><td class=\"sign\">+</td><td><mark>&lt;i&gt;</mark></td></tr>"));
+
+        let output = diff_html("a\nb\n", "a\nb\n").unwrap();
+        assert!(output
+            .starts_with("<details class=\"unchanged\"><summary>2 unchanged lines</summary>\n"));
+        assert!(!output.contains("<tr class=\"delete\">"));
+    }
+
+    #[test]
+    fn hunks_html_should_render_json_changes() {
+        let v1 = serde_json::json!({"id": 1, "title": "<a>"});
+        let v2 = serde_json::json!({"id": 1, "title": "<b>"});
+        let output = hunks_html(&json_hunks(&v1, &v2)).unwrap();
+        assert_eq!(
+            output,
+            "<table class=\"diff\">\n\
+            <tr class=\"delete\"><td class=\"num\"></td><td class=\"num\"></td><td class=\"sign\">-</td><td>$.title: &quot;&lt;a&gt;&quot;</td></tr>\n\
+            <tr class=\"insert\"><td class=\"num\"></td><td class=\"num\"></td><td class=\"sign\">+</td><td>$.title: &quot;&lt;b&gt;&quot;</td></tr>\n\
+            </table>\n"
+        );
+    }
+}
@@ -0,0 +1,366 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+/// A path into a JSON value.
+///
+/// Two syntaxes are accepted:
+/// - JSON Pointer (RFC 6901), e.g. `/data/meta/request_id`
+/// - dotted form with optional `$` root and wildcards, e.g. `data.meta.request_id`,
+///   `items[*].updated_at`, `$.items[3].price` or `items.*.updated_at`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// object key; also matches an array index if the key is a number
+    Key(String),
+    /// array index written as `[n]`
+    Index(usize),
+    /// every key of an object or every element of an array
+    Wildcard,
+}
+
+impl JsonPath {
+    pub fn new(segments: Vec<Segment>) -> Self {
+        Self { segments }
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// remove every value matching the path, returns how many values were removed
+    pub fn remove(&self, value: &mut Value) -> usize {
+        remove_at(value, &self.segments)
+    }
+
+    /// every value matching the path
+    pub fn select_mut<'a>(&self, value: &'a mut Value) -> Vec<&'a mut Value> {
+        let mut values = vec![value];
+        for seg in &self.segments {
+            values = values
+                .into_iter()
+                .flat_map(|v| children_mut(v, seg))
+                .collect();
+        }
+        values
+    }
+
+    fn parse_pointer(s: &str) -> Result<Self> {
+        let segments = s[1..]
+            .split('/')
+            .map(|seg| Segment::Key(seg.replace("~1", "/").replace("~0", "~")))
+            .collect();
+        Ok(Self::new(segments))
+    }
+
+    fn parse_dotted(s: &str) -> Result<Self> {
+        let s = s.strip_prefix('$').unwrap_or(s);
+        let mut segments = vec![];
+        let mut chars = s.chars();
+        let mut key = String::new();
+        // whether the previous segment ended with `]`, which allows `a[0][1]` and `a[0].b`
+        let mut after_bracket = false;
+
+        while let Some(c) = chars.next() {
+            match c {
+                '.' => {
+                    if !key.is_empty() {
+                        segments.push(Segment::from_key(std::mem::take(&mut key)));
+                    } else if !after_bracket && !segments.is_empty() {
+                        return Err(anyhow!("Invalid json path `{}`: empty segment", s));
+                    }
+                    after_bracket = false;
+                }
+                '[' => {
+                    if !key.is_empty() {
+                        segments.push(Segment::from_key(std::mem::take(&mut key)));
+                    }
+                    let mut index = String::new();
+                    loop {
+                        match chars.next() {
+                            Some(']') => break,
+                            Some(c) => index.push(c),
+                            None => return Err(anyhow!("Invalid json path `{}`: missing `]`", s)),
+                        }
+                    }
+                    let seg = match index.trim() {
+                        "*" => Segment::Wildcard,
+                        v => Segment::Index(v.parse().map_err(|_| {
+                            anyhow!("Invalid json path `{}`: bad array index `{}`", s, v)
+                        })?),
+                    };
+                    segments.push(seg);
+                    after_bracket = true;
+                }
+                ']' => return Err(anyhow!("Invalid json path `{}`: unexpected `]`", s)),
+                c => {
+                    if after_bracket {
+                        return Err(anyhow!("Invalid json path `{}`: expect `.` after `]`", s));
+                    }
+                    key.push(c)
+                }
+            }
+        }
+
+        if !key.is_empty() {
+            segments.push(Segment::from_key(key));
+        } else if !after_bracket {
+            return Err(anyhow!("Invalid json path `{}`: empty segment", s));
+        }
+
+        Ok(Self::new(segments))
+    }
+}
+
+impl Segment {
+    fn from_key(key: String) -> Self {
+        if key == "*" {
+            Segment::Wildcard
+        } else {
+            Segment::Key(key)
+        }
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(anyhow!("Invalid json path: empty path"));
+        }
+        if s.starts_with('/') {
+            Self::parse_pointer(s)
+        } else {
+            Self::parse_dotted(s)
+        }
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "$")?;
+        for seg in &self.segments {
+            match seg {
+                Segment::Key(k) => write!(f, ".{}", k)?,
+                Segment::Index(i) => write!(f, "[{}]", i)?,
+                Segment::Wildcard => write!(f, "[*]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A single difference between two JSON values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonChange {
+    Added(JsonPath, Value),
+    Removed(JsonPath, Value),
+    Changed(JsonPath, Value, Value),
+}
+
+/// walk both values and report every added, removed and changed value by path
+pub fn diff_values(old: &Value, new: &Value) -> Vec<JsonChange> {
+    let mut changes = vec![];
+    diff_at(&mut vec![], old, new, &mut changes);
+    changes
+}
+
+fn diff_at(path: &mut Vec<Segment>, old: &Value, new: &Value, changes: &mut Vec<JsonChange>) {
+    match (old, new) {
+        (Value::Object(o1), Value::Object(o2)) => {
+            for (k, v1) in o1 {
+                path.push(Segment::Key(k.clone()));
+                match o2.get(k) {
+                    Some(v2) => diff_at(path, v1, v2, changes),
+                    None => {
+                        changes.push(JsonChange::Removed(JsonPath::new(path.clone()), v1.clone()))
+                    }
+                }
+                path.pop();
+            }
+            for (k, v2) in o2 {
+                if !o1.contains_key(k) {
+                    path.push(Segment::Key(k.clone()));
+                    changes.push(JsonChange::Added(JsonPath::new(path.clone()), v2.clone()));
+                    path.pop();
+                }
+            }
+        }
+        (Value::Array(a1), Value::Array(a2)) => {
+            for i in 0..a1.len().max(a2.len()) {
+                path.push(Segment::Index(i));
+                match (a1.get(i), a2.get(i)) {
+                    (Some(v1), Some(v2)) => diff_at(path, v1, v2, changes),
+                    (Some(v1), None) => {
+                        changes.push(JsonChange::Removed(JsonPath::new(path.clone()), v1.clone()))
+                    }
+                    (None, Some(v2)) => {
+                        changes.push(JsonChange::Added(JsonPath::new(path.clone()), v2.clone()))
+                    }
+                    (None, None) => unreachable!(),
+                }
+                path.pop();
+            }
+        }
+        (v1, v2) if v1 != v2 => changes.push(JsonChange::Changed(
+            JsonPath::new(path.clone()),
+            v1.clone(),
+            v2.clone(),
+        )),
+        _ => {}
+    }
+}
+
+fn children_mut<'a>(value: &'a mut Value, seg: &Segment) -> Vec<&'a mut Value> {
+    match (value, seg) {
+        (Value::Object(obj), Segment::Key(k)) => obj.get_mut(k).into_iter().collect(),
+        (Value::Object(obj), Segment::Wildcard) => obj.values_mut().collect(),
+        (Value::Array(arr), Segment::Index(i)) => arr.get_mut(*i).into_iter().collect(),
+        (Value::Array(arr), Segment::Key(k)) => match k.parse::<usize>() {
+            Ok(i) => arr.get_mut(i).into_iter().collect(),
+            Err(_) => vec![],
+        },
+        (Value::Array(arr), Segment::Wildcard) => arr.iter_mut().collect(),
+        _ => vec![],
+    }
+}
+
+fn remove_at(value: &mut Value, segments: &[Segment]) -> usize {
+    match segments {
+        [] => 0,
+        [last] => match (value, last) {
+            (Value::Object(obj), Segment::Key(k)) => obj.remove(k).map_or(0, |_| 1),
+            (Value::Object(obj), Segment::Wildcard) => {
+                let n = obj.len();
+                obj.clear();
+                n
+            }
+            (Value::Array(arr), Segment::Index(i)) if *i < arr.len() => {
+                arr.remove(*i);
+                1
+            }
+            (Value::Array(arr), Segment::Key(k)) => match k.parse::<usize>() {
+                Ok(i) if i < arr.len() => {
+                    arr.remove(i);
+                    1
+                }
+                _ => 0,
+            },
+            (Value::Array(arr), Segment::Wildcard) => {
+                let n = arr.len();
+                arr.clear();
+                n
+            }
+            _ => 0,
+        },
+        [first, rest @ ..] => children_mut(value, first)
+            .into_iter()
+            .map(|v| remove_at(v, rest))
+            .sum(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_dotted_path_should_work() {
+        let path: JsonPath = "$.items[*].meta.id".parse().unwrap();
+        assert_eq!(
+            path.segments(),
+            &[
+                Segment::Key("items".into()),
+                Segment::Wildcard,
+                Segment::Key("meta".into()),
+                Segment::Key("id".into()),
+            ]
+        );
+        assert_eq!(path.to_string(), "$.items[*].meta.id");
+
+        let path: JsonPath = "items.*.list[2][0]".parse().unwrap();
+        assert_eq!(path.to_string(), "$.items[*].list[2][0]");
+    }
+
+    #[test]
+    fn parse_json_pointer_should_work() {
+        let path: JsonPath = "/data/a~1b/0".parse().unwrap();
+        assert_eq!(
+            path.segments(),
+            &[
+                Segment::Key("data".into()),
+                Segment::Key("a/b".into()),
+                Segment::Key("0".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_bad_path_should_fail() {
+        assert!("".parse::<JsonPath>().is_err());
+        assert!("a..b".parse::<JsonPath>().is_err());
+        assert!("a[x]".parse::<JsonPath>().is_err());
+        assert!("a[0".parse::<JsonPath>().is_err());
+        assert!("a[0]b".parse::<JsonPath>().is_err());
+    }
+
+    #[test]
+    fn remove_nested_path_should_work() {
+        let mut v = json!({
+            "data": {"meta": {"request_id": "abc", "page": 1}},
+            "items": [
+                {"id": 1, "updated_at": "2023"},
+                {"id": 2, "updated_at": "2024"},
+            ],
+        });
+
+        let path: JsonPath = "data.meta.request_id".parse().unwrap();
+        assert_eq!(path.remove(&mut v), 1);
+        let path: JsonPath = "items[*].updated_at".parse().unwrap();
+        assert_eq!(path.remove(&mut v), 2);
+        let path: JsonPath = "/items/1".parse().unwrap();
+        assert_eq!(path.remove(&mut v), 1);
+
+        assert_eq!(
+            v,
+            json!({
+                "data": {"meta": {"page": 1}},
+                "items": [{"id": 1}],
+            })
+        );
+    }
+
+    #[test]
+    fn diff_values_should_work() {
+        let old = json!({"a": 1, "b": {"c": true}, "items": [{"price": 10}, {"price": 5}]});
+        let new = json!({"a": 1, "d": null, "items": [{"price": 12}]});
+
+        let changes = diff_values(&old, &new);
+        assert_eq!(
+            changes,
+            vec![
+                JsonChange::Removed("b".parse().unwrap(), json!({"c": true})),
+                JsonChange::Changed("items[0].price".parse().unwrap(), json!(10), json!(12)),
+                JsonChange::Removed("items[1]".parse().unwrap(), json!({"price": 5})),
+                JsonChange::Added("d".parse().unwrap(), json!(null)),
+            ]
+        );
+        assert!(diff_values(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn remove_missing_path_should_return_zero() {
+        let mut v = json!({"data": {"id": 1}});
+        let path: JsonPath = "data.meta.request_id".parse().unwrap();
+        assert_eq!(path.remove(&mut v), 0);
+        assert_eq!(v, json!({"data": {"id": 1}}));
+    }
+}
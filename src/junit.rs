@@ -0,0 +1,200 @@
+use crate::{DiffProfile, ExitStatus, ExtraArgs, InvalidConfig, ValidateConfig};
+use std::fmt::Write as _;
+use std::time::{Duration, Instant};
+
+/// Result of diffing one profile, reported as a junit test case
+#[derive(Debug)]
+pub struct TestCase {
+    pub name: String,
+    pub elapsed: Duration,
+    pub outcome: TestOutcome,
+}
+
+#[derive(Debug)]
+pub enum TestOutcome {
+    /// responses are identical
+    Passed,
+    /// responses differ, with the rendered diff
+    Failed(String),
+    /// profile is invalid or the requests failed
+    Error(anyhow::Error),
+}
+
+impl TestCase {
+    /// validate the profile and diff its responses
+    pub async fn run(name: &str, profile: &DiffProfile, args: &ExtraArgs) -> Self {
+        let start = Instant::now();
+        let outcome = match profile.validate_profile(name) {
+            Ok(_) => match profile.diff(args.clone()).await {
+                Ok(output) if output.changed => {
+                    TestOutcome::Failed(console::strip_ansi_codes(&output.text).to_string())
+                }
+                Ok(_) => TestOutcome::Passed,
+                Err(e) => TestOutcome::Error(e),
+            },
+            Err(e) => TestOutcome::Error(InvalidConfig(e).into()),
+        };
+
+        Self {
+            name: name.to_string(),
+            elapsed: start.elapsed(),
+            outcome,
+        }
+    }
+
+    /// a profile which could not be loaded
+    pub fn error(name: &str, error: anyhow::Error) -> Self {
+        Self {
+            name: name.to_string(),
+            elapsed: Duration::ZERO,
+            outcome: TestOutcome::Error(error),
+        }
+    }
+}
+
+/// Exit status for a batch run: any error wins over differences
+pub fn junit_exit_status(cases: &[TestCase]) -> ExitStatus {
+    let error = cases.iter().find_map(|case| match &case.outcome {
+        TestOutcome::Error(e) => Some(e),
+        _ => None,
+    });
+    match error {
+        Some(e) => ExitStatus::from_error(e),
+        None if cases
+            .iter()
+            .any(|case| matches!(case.outcome, TestOutcome::Failed(_))) =>
+        {
+            ExitStatus::Different
+        }
+        None => ExitStatus::Ok,
+    }
+}
+
+/// Render the test cases as a junit xml report
+pub fn junit_report(suite: &str, cases: &[TestCase]) -> String {
+    let failures = cases
+        .iter()
+        .filter(|case| matches!(case.outcome, TestOutcome::Failed(_)))
+        .count();
+    let errors = cases
+        .iter()
+        .filter(|case| matches!(case.outcome, TestOutcome::Error(_)))
+        .count();
+    let time: Duration = cases.iter().map(|case| case.elapsed).sum();
+
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let attrs = format!(
+        "tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\"",
+        cases.len(),
+        failures,
+        errors,
+        time.as_secs_f64()
+    );
+    let _ = writeln!(output, "<testsuites name=\"xdiff\" {}>", attrs);
+    let _ = writeln!(
+        output,
+        "  <testsuite name=\"{}\" {}>",
+        escape_xml(suite),
+        attrs
+    );
+
+    for case in cases {
+        let _ = write!(
+            output,
+            "    <testcase name=\"{}\" classname=\"xdiff\" time=\"{:.3}\"",
+            escape_xml(&case.name),
+            case.elapsed.as_secs_f64()
+        );
+        match &case.outcome {
+            TestOutcome::Passed => output.push_str("/>\n"),
+            TestOutcome::Failed(diff) => {
+                let _ = writeln!(
+                    output,
+                    ">\n      <failure message=\"responses differ\" type=\"diff\">{}</failure>\n    </testcase>",
+                    cdata(diff)
+                );
+            }
+            TestOutcome::Error(e) => {
+                let _ = writeln!(
+                    output,
+                    ">\n      <error message=\"{}\" type=\"error\">{}</error>\n    </testcase>",
+                    escape_xml(&e.to_string()),
+                    cdata(&format!("{:?}", e))
+                );
+            }
+        }
+    }
+
+    output.push_str("  </testsuite>\n</testsuites>\n");
+    output
+}
+
+fn cdata(s: &str) -> String {
+    format!("<![CDATA[{}]]>", s.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut output = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&apos;"),
+            '\n' => output.push_str("&#10;"),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn junit_report_should_work() {
+        let cases = vec![
+            TestCase {
+                name: "same".into(),
+                elapsed: Duration::from_millis(100),
+                outcome: TestOutcome::Passed,
+            },
+            TestCase {
+                name: "diff".into(),
+                elapsed: Duration::from_millis(200),
+                outcome: TestOutcome::Failed("-a\n+b ]]> <c>\n".into()),
+            },
+            TestCase {
+                name: "bad".into(),
+                elapsed: Duration::from_millis(0),
+                outcome: TestOutcome::Error(
+                    InvalidConfig(anyhow::anyhow!("Params must be an \"object\"")).into(),
+                ),
+            },
+        ];
+
+        assert_eq!(
+            junit_report("fixtures/test.yml", &cases),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="xdiff" tests="3" failures="1" errors="1" time="0.300">
+  <testsuite name="fixtures/test.yml" tests="3" failures="1" errors="1" time="0.300">
+    <testcase name="same" classname="xdiff" time="0.100"/>
+    <testcase name="diff" classname="xdiff" time="0.200">
+      <failure message="responses differ" type="diff"><![CDATA[-a
++b ]]]]><![CDATA[> <c>
+]]></failure>
+    </testcase>
+    <testcase name="bad" classname="xdiff" time="0.000">
+      <error message="Params must be an &quot;object&quot;" type="error"><![CDATA[Params must be an "object"]]></error>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+        assert_eq!(junit_exit_status(&cases), ExitStatus::ConfigError);
+        assert_eq!(junit_exit_status(&cases[..2]), ExitStatus::Different);
+        assert_eq!(junit_exit_status(&cases[..1]), ExitStatus::Ok);
+    }
+}
@@ -1,12 +1,28 @@
 pub mod cli;
+mod completions;
 pub mod config;
+mod html;
+pub mod json_path;
+mod junit;
+mod report;
 mod utils;
 
+pub use completions::completions;
 pub use config::{
-    get_body_text, get_headers_text, get_status_text, is_default, DiffConfig, DiffProfile,
-    LoadConfig, RequestConfig, RequestProfile, ResponseProfile, ValidateConfig,
+    get_body_text, get_headers_text, get_status_text, is_default, prompt_vars, render_template,
+    template_placeholders, ApiKeyLocation, Auth, BodyFormat, BuiltinMask, ConfigCheck,
+    ConfigFormat, ConfigIssue, ConfigSource, DiffConfig, DiffMode, DiffOutput, DiffProfile, Layout,
+    LoadConfig, Mask, MaskPattern, RequestConfig, RequestProfile, ResponseProfile, ResponseText,
+    Retry, RetryPolicy, Secret, TlsConfig, TlsVersion, ValidateConfig,
+};
+pub use html::{diff_html, html_report};
+pub use json_path::JsonPath;
+pub use junit::{junit_exit_status, junit_report, TestCase, TestOutcome};
+pub use report::{diff_hunks, json_hunks, DiffReport, Hunk, HunkLine, LineTag, ResponseReport};
+pub use utils::{
+    diff_json, diff_text, diff_text_side_by_side, highlight_html, highlight_text, print_warning,
+    process_error_output, terminal_width, ExitStatus, InvalidConfig,
 };
-pub use utils::{diff_text, highlight_text, process_error_output};
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct ExtraArgs {
@@ -0,0 +1,174 @@
+use serde::Serialize;
+use serde_json::Value;
+use similar::{ChangeTag, TextDiff};
+
+use crate::json_path::{diff_values, JsonChange};
+use crate::Retry;
+
+/// Machine-readable result of diffing the two responses of a profile
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct DiffReport {
+    pub profile: String,
+    /// whether the responses differ after the response profile is applied
+    pub changed: bool,
+    pub req1: ResponseReport,
+    pub req2: ResponseReport,
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ResponseReport {
+    pub method: String,
+    /// url with the query params resolved
+    pub url: String,
+    pub status: u16,
+    /// headers which are not skipped
+    pub headers: Vec<(String, String)>,
+    /// body after `skip_body` is applied
+    pub body: String,
+    /// size of the raw body in bytes
+    pub size: usize,
+    pub elapsed_ms: u64,
+    /// failed attempts which were retried, elapsed time includes them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub retries: Vec<Retry>,
+}
+
+/// A group of changes with the unchanged lines around them
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Hunk {
+    pub lines: Vec<HunkLine>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct HunkLine {
+    pub tag: LineTag,
+    /// 1-based line number in the first response, none for inserted lines and json changes
+    pub old_line: Option<usize>,
+    /// 1-based line number in the second response, none for deleted lines and json changes
+    pub new_line: Option<usize>,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineTag {
+    Equal,
+    Delete,
+    Insert,
+}
+
+impl From<ChangeTag> for LineTag {
+    fn from(tag: ChangeTag) -> Self {
+        match tag {
+            ChangeTag::Equal => LineTag::Equal,
+            ChangeTag::Delete => LineTag::Delete,
+            ChangeTag::Insert => LineTag::Insert,
+        }
+    }
+}
+
+/// group the line changes the same way as `diff_text` does
+pub fn diff_hunks(text1: &str, text2: &str) -> Vec<Hunk> {
+    let diff = TextDiff::from_lines(text1, text2);
+
+    diff.grouped_ops(3)
+        .iter()
+        .map(|group| {
+            let lines = group
+                .iter()
+                .flat_map(|op| diff.iter_changes(op))
+                .map(|change| HunkLine {
+                    tag: change.tag().into(),
+                    old_line: change.old_index().map(|i| i + 1),
+                    new_line: change.new_index().map(|i| i + 1),
+                    value: change.to_string_lossy().trim_end_matches('\n').to_string(),
+                })
+                .collect();
+            Hunk { lines }
+        })
+        .collect()
+}
+
+/// the changes of two json bodies as one hunk of `path: value` lines, the same as
+/// `diff_json` prints them. A changed value is deleted and inserted
+pub fn json_hunks(v1: &Value, v2: &Value) -> Vec<Hunk> {
+    let line = |tag, path: &dyn std::fmt::Display, value: &Value| HunkLine {
+        tag,
+        old_line: None,
+        new_line: None,
+        value: format!("{}: {}", path, value),
+    };
+    let lines: Vec<_> = diff_values(v1, v2)
+        .into_iter()
+        .flat_map(|change| match change {
+            JsonChange::Added(path, v) => vec![line(LineTag::Insert, &path, &v)],
+            JsonChange::Removed(path, v) => vec![line(LineTag::Delete, &path, &v)],
+            JsonChange::Changed(path, old, new) => vec![
+                line(LineTag::Delete, &path, &old),
+                line(LineTag::Insert, &path, &new),
+            ],
+        })
+        .collect();
+    match lines.is_empty() {
+        true => vec![],
+        false => vec![Hunk { lines }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_hunks_should_work() {
+        let hunks = diff_hunks("foo\nbar\n", "foo\nbaz\n");
+        assert_eq!(
+            hunks,
+            vec![Hunk {
+                lines: vec![
+                    HunkLine {
+                        tag: LineTag::Equal,
+                        old_line: Some(1),
+                        new_line: Some(1),
+                        value: "foo".into(),
+                    },
+                    HunkLine {
+                        tag: LineTag::Delete,
+                        old_line: Some(2),
+                        new_line: None,
+                        value: "bar".into(),
+                    },
+                    HunkLine {
+                        tag: LineTag::Insert,
+                        old_line: None,
+                        new_line: Some(2),
+                        value: "baz".into(),
+                    },
+                ],
+            }]
+        );
+        assert!(diff_hunks("foo\n", "foo\n").is_empty());
+    }
+
+    #[test]
+    fn json_hunks_should_list_changes_by_path() {
+        let v1 = serde_json::json!({"id": 1, "tags": ["a"], "title": "todo"});
+        let v2 = serde_json::json!({"id": 2, "tags": ["a", "b"], "title": "todo"});
+        let hunks = json_hunks(&v1, &v2);
+        let values: Vec<_> = hunks[0]
+            .lines
+            .iter()
+            .map(|line| (line.tag, line.value.as_str()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                (LineTag::Delete, "$.id: 1"),
+                (LineTag::Insert, "$.id: 2"),
+                (LineTag::Insert, "$.tags[1]: \"b\""),
+            ]
+        );
+        assert!(json_hunks(&v1, &v1).is_empty());
+    }
+}
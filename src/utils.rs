@@ -1,11 +1,15 @@
+use crate::json_path::{diff_values, JsonChange};
 use anyhow::Result;
-use console::{style, Style};
+use console::{measure_text_width, style, Style, Term};
+use serde_json::Value;
 use similar::{ChangeTag, TextDiff};
 use std::fmt;
 use std::fmt::Write as _;
 use std::io::Write as _;
+use std::process::ExitCode;
 use syntect::easy::HighlightLines;
 use syntect::highlighting::ThemeSet;
+use syntect::html::highlighted_html_for_string;
 use syntect::parsing::SyntaxSet;
 use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
 
@@ -62,7 +66,7 @@ pub fn diff_text(text1: &str, text2: &str) -> Result<String> {
                 }
                 if change.missing_newline() {
                     // println!();
-                    output.push_str("\n");
+                    output.push('\n');
                 }
             }
         }
@@ -70,6 +74,181 @@ pub fn diff_text(text1: &str, text2: &str) -> Result<String> {
     Ok(output)
 }
 
+/// width of the side-by-side diff when stdout is not a terminal
+const DEFAULT_WIDTH: usize = 160;
+
+/// width of the terminal, or a default width if stdout is not a terminal
+pub fn terminal_width() -> usize {
+    Term::stdout()
+        .size_checked()
+        .map(|(_, w)| w as usize)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// One side of a row in the side-by-side diff
+struct Cell {
+    index: Option<usize>,
+    style: Style,
+    segments: Vec<(bool, String)>,
+}
+
+/// Render the diff in two columns fitted to `width`, long lines are wrapped
+pub fn diff_text_side_by_side(text1: &str, text2: &str, width: usize) -> Result<String> {
+    let mut output = String::new();
+    // each side has a `NNNN ` gutter, and the columns are separated by ` | `
+    let col = (width.saturating_sub(13) / 2).max(10);
+
+    let diff = TextDiff::from_lines(text1, text2);
+
+    for (idx, group) in diff.grouped_ops(3).iter().enumerate() {
+        if idx > 0 {
+            writeln!(&mut output, "{:-^1$}", "-", col * 2 + 13)?;
+        }
+        for op in group {
+            // pair deleted lines with inserted lines so that replaced lines are side by side
+            let mut old = vec![];
+            let mut new = vec![];
+            for change in diff.iter_inline_changes(op) {
+                let segments: Vec<_> = change
+                    .iter_strings_lossy()
+                    .map(|(emphasized, value)| {
+                        (emphasized, value.trim_end_matches(['\r', '\n']).to_string())
+                    })
+                    .collect();
+                match change.tag() {
+                    ChangeTag::Equal => {
+                        old.push(Cell {
+                            index: change.old_index(),
+                            style: Style::new().dim(),
+                            segments: segments.clone(),
+                        });
+                        new.push(Cell {
+                            index: change.new_index(),
+                            style: Style::new().dim(),
+                            segments,
+                        });
+                    }
+                    ChangeTag::Delete => old.push(Cell {
+                        index: change.old_index(),
+                        style: Style::new().red(),
+                        segments,
+                    }),
+                    ChangeTag::Insert => new.push(Cell {
+                        index: change.new_index(),
+                        style: Style::new().green(),
+                        segments,
+                    }),
+                }
+            }
+
+            for i in 0..old.len().max(new.len()) {
+                let left = wrap_cell(old.get(i), col, true);
+                let right = wrap_cell(new.get(i), col, false);
+                for j in 0..left.len().max(right.len()) {
+                    let gutter = |cell: Option<&Cell>| match (j, cell) {
+                        (0, Some(cell)) => Line(cell.index),
+                        _ => Line(None),
+                    };
+                    writeln!(
+                        &mut output,
+                        "{} {} | {} {}",
+                        style(gutter(old.get(i))).dim(),
+                        left.get(j).map_or(" ".repeat(col), |s| s.clone()),
+                        style(gutter(new.get(i))).dim(),
+                        right.get(j).map_or("", |s| s.as_str()),
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// split the cell into lines of `width` columns, keeping the inline emphasis
+fn wrap_cell(cell: Option<&Cell>, width: usize, pad: bool) -> Vec<String> {
+    let Some(cell) = cell else {
+        return vec![];
+    };
+
+    let mut lines = vec![];
+    let mut line = String::new();
+    let mut line_width = 0;
+    let render = |line: &mut String, run: &mut String, emphasized: bool| {
+        if !run.is_empty() {
+            let s = cell.style.apply_to(std::mem::take(run));
+            if emphasized {
+                line.push_str(&s.underlined().on_black().to_string());
+            } else {
+                line.push_str(&s.to_string());
+            }
+        }
+    };
+
+    for (emphasized, value) in &cell.segments {
+        let mut run = String::new();
+        for c in value.chars() {
+            let w = measure_text_width(c.encode_utf8(&mut [0; 4]));
+            if line_width + w > width {
+                render(&mut line, &mut run, *emphasized);
+                if pad {
+                    line.push_str(&" ".repeat(width - line_width));
+                }
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+            }
+            run.push(c);
+            line_width += w;
+        }
+        render(&mut line, &mut run, *emphasized);
+    }
+    if pad {
+        line.push_str(&" ".repeat(width - line_width));
+    }
+    lines.push(line);
+    lines
+}
+
+pub fn diff_json(v1: &Value, v2: &Value) -> Result<String> {
+    let mut output = String::new();
+
+    for change in diff_values(v1, v2) {
+        match change {
+            JsonChange::Added(path, v) => {
+                let s = Style::new().green();
+                writeln!(
+                    &mut output,
+                    "{} {}: {}",
+                    s.apply_to("+").bold(),
+                    path,
+                    s.apply_to(serde_json::to_string(&v)?)
+                )?;
+            }
+            JsonChange::Removed(path, v) => {
+                let s = Style::new().red();
+                writeln!(
+                    &mut output,
+                    "{} {}: {}",
+                    s.apply_to("-").bold(),
+                    path,
+                    s.apply_to(serde_json::to_string(&v)?)
+                )?;
+            }
+            JsonChange::Changed(path, old, new) => {
+                writeln!(
+                    &mut output,
+                    "{} {}: {} -> {}",
+                    Style::new().yellow().apply_to("~").bold(),
+                    path,
+                    Style::new().red().apply_to(serde_json::to_string(&old)?),
+                    Style::new().green().apply_to(serde_json::to_string(&new)?),
+                )?;
+            }
+        }
+    }
+
+    Ok(output)
+}
+
 pub fn highlight_text(text: &str, extension: &str, theme: Option<&str>) -> Result<String> {
     // Load these once at the start of your program
     let ps = SyntaxSet::load_defaults_newlines();
@@ -80,10 +259,7 @@ pub fn highlight_text(text: &str, extension: &str, theme: Option<&str>) -> Resul
     } else {
         ps.find_syntax_plain_text()
     };
-    let mut h = HighlightLines::new(
-        syntax,
-        &ts.themes[theme.unwrap_or_else(|| "Solarized (dark)")],
-    );
+    let mut h = HighlightLines::new(syntax, &ts.themes[theme.unwrap_or("Solarized (dark)")]);
 
     let mut output = String::new();
 
@@ -96,22 +272,105 @@ pub fn highlight_text(text: &str, extension: &str, theme: Option<&str>) -> Resul
     Ok(output)
 }
 
-pub fn process_error_output(result: Result<()>) -> Result<()> {
+/// Exit status of the binaries, so that scripts could act on the result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// responses are identical, or the request succeeded
+    Ok = 0,
+    /// responses differ
+    Different = 1,
+    /// config could not be found, loaded or validated
+    ConfigError = 2,
+    /// request could not be sent or the response could not be read
+    NetworkError = 3,
+    /// response status is 4xx, only with `--check-status`
+    ClientError = 4,
+    /// response status is 5xx, only with `--check-status`
+    ServerError = 5,
+    /// any other error, e.g. a response body which could not be parsed or output which could
+    /// not be written
+    Failure = 6,
+}
+
+impl ExitStatus {
+    pub fn from_error(e: &anyhow::Error) -> Self {
+        if e.chain().any(|e| e.is::<reqwest::Error>()) {
+            ExitStatus::NetworkError
+        } else if e.chain().any(|e| e.is::<InvalidConfig>()) {
+            ExitStatus::ConfigError
+        } else {
+            ExitStatus::Failure
+        }
+    }
+}
+
+/// Marks an error as a config error, reported with `ExitStatus::ConfigError`. It is shown
+/// exactly as the error it wraps
+#[derive(Debug)]
+pub struct InvalidConfig(pub anyhow::Error);
+
+impl fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidConfig {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
+pub fn highlight_html(text: &str, extension: &str, theme: Option<&str>) -> Result<String> {
+    let ps = SyntaxSet::load_defaults_newlines();
+    let ts = ThemeSet::load_defaults();
+
+    let syntax = ps
+        .find_syntax_by_extension(extension)
+        .unwrap_or_else(|| ps.find_syntax_plain_text());
+
+    Ok(highlighted_html_for_string(
+        text,
+        &ps,
+        syntax,
+        &ts.themes[theme.unwrap_or("InspiredGitHub")],
+    )?)
+}
+
+pub fn process_error_output(result: Result<ExitStatus>) -> ExitCode {
     match result {
-        Ok(_) => {}
+        Ok(status) => status.into(),
         Err(e) => {
             let stderr = std::io::stderr();
             let mut stderr = stderr.lock();
+            // nothing else we could do if stderr is gone, the exit code still reports the error
             if atty::is(atty::Stream::Stderr) {
                 let s = Style::new().red();
-                writeln!(stderr, "{}", s.apply_to(format!("{:?}", e)))?;
+                let _ = writeln!(stderr, "{}", s.apply_to(format!("{:?}", e)));
             } else {
-                writeln!(stderr, "{:?}", e)?;
+                let _ = writeln!(stderr, "{:?}", e);
             }
+            ExitStatus::from_error(&e).into()
         }
     }
+}
 
-    Ok(())
+pub fn print_warning(msg: &str) {
+    let stderr = std::io::stderr();
+    let mut stderr = stderr.lock();
+    // a warning should never turn into an error, so ignore write failures here
+    if atty::is(atty::Stream::Stderr) {
+        let s = Style::new().yellow();
+        let _ = writeln!(stderr, "{}", s.apply_to(format!("warning: {}", msg)));
+    } else {
+        let _ = writeln!(stderr, "warning: {}", msg);
+    }
 }
 
 #[cfg(test)]
@@ -131,6 +390,45 @@ mod tests {
         assert_eq!(diff_text(text1, text2).unwrap(), expected);
     }
 
+    #[test]
+    fn diff_text_side_by_side_should_work() {
+        let text1 = "foo\nbar\nabcdefghijklmno\n";
+        let text2 = "foo\nbaz\n";
+
+        assert_eq!(
+            diff_text_side_by_side(text1, text2, 33).unwrap(),
+            "1    foo        | 1    foo\n\
+             2    bar        | 2    baz\n\
+             3    abcdefghij |      \n\
+             \x20    klmno      |      \n"
+        );
+    }
+
+    #[test]
+    fn diff_json_should_work() {
+        let v1 = json!({"id": 1, "items": [{"price": 10}], "title": "foo"});
+        let v2 = json!({"id": 1, "items": [{"price": 12}, {"price": 1}], "done": false});
+
+        assert_eq!(
+            diff_json(&v1, &v2).unwrap(),
+            "~ $.items[0].price: 10 -> 12\n+ $.items[1]: {\"price\":1}\n- $.title: \"foo\"\n+ $.done: false\n"
+        );
+    }
+
+    #[test]
+    fn exit_status_from_error_should_work() {
+        let e = anyhow::anyhow!("bad config").context("failed to validate profile: todo");
+        let e = anyhow::Error::from(InvalidConfig(e)).context("failed to run");
+        assert_eq!(ExitStatus::from_error(&e), ExitStatus::ConfigError);
+        assert_eq!(
+            format!("{:?}", e),
+            "failed to run\n\nCaused by:\n    0: failed to validate profile: todo\n    1: bad config"
+        );
+
+        let e = anyhow::anyhow!("failed to parse html body");
+        assert_eq!(ExitStatus::from_error(&e), ExitStatus::Failure);
+    }
+
     #[test]
     fn highlight_text_should_work() {
         let v = json!({
@@ -142,4 +440,4 @@ mod tests {
 
         assert_eq!(highlight_text(&text, "json", None).unwrap(), expected);
     }
-}
\ No newline at end of file
+}